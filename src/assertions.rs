@@ -0,0 +1,364 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structured assertions over traces, for unit tests.
+//!
+//! Instead of searching the rendered trace for substrings, assertions are made on the
+//! parser invocations rebuilt from its events. Parsers are selected by name: their context,
+//! or their normalized location (see [normalize_location]).
+//!
+//! When an assertion fails, it panics with the subtrees of the relevant invocations.
+//!
+//! ```
+//! use nom_tracer::{assertions::TraceAssertions, traces::Trace};
+//! # use nom::error::VerboseError;
+//!
+//! let mut trace = Trace::default();
+//! trace.open(None, "42", "number", false);
+//! trace.close::<_, _, VerboseError<&str>>(None, "42", "number", &Ok(("", "42")), false);
+//!
+//! TraceAssertions::new(&trace)
+//!     .max_invocations(1)
+//!     .parser("number")
+//!     .invoked_with("42")
+//!     .never_failed();
+//! ```
+
+use {
+    crate::{
+        events::{TraceEvent, TraceEventType},
+        frames::Frame,
+        snapshot::normalize_location,
+        traces::Trace,
+    },
+    std::collections::BTreeMap,
+};
+
+/// The maximum number of subtrees printed when an assertion fails.
+const MAX_REPORTED_FRAMES: usize = 5;
+
+/// Assertions over all the parser invocations of a trace.
+pub struct TraceAssertions<'a> {
+    roots: Vec<Frame<'a>>,
+}
+
+impl<'a> TraceAssertions<'a> {
+    /// Rebuilds the parser invocations of a trace to make assertions on them.
+    pub fn new(trace: &'a Trace) -> Self {
+        Self {
+            roots: Frame::from_events(&trace.events),
+        }
+    }
+
+    /// Selects the invocations of the parser with the given name, its context or its
+    /// normalized location.
+    pub fn parser(&self, name: &str) -> ParserAssertions<'_, 'a> {
+        let frames = self
+            .invocations()
+            .filter(|frame| {
+                frame.open.context == Some(name) || normalize_location(frame.open.location) == name
+            })
+            .collect();
+
+        ParserAssertions {
+            name: name.to_string(),
+            frames,
+        }
+    }
+
+    /// Asserts that no parser was invoked more than `max` times.
+    pub fn max_invocations(&self, max: usize) -> &Self {
+        let mut counts: BTreeMap<&str, Vec<&Frame<'a>>> = BTreeMap::new();
+        for frame in self.invocations() {
+            let name = frame
+                .open
+                .context
+                .unwrap_or(normalize_location(frame.open.location));
+            counts.entry(name).or_default().push(frame);
+        }
+
+        if let Some((name, frames)) = counts.iter().find(|(_, frames)| frames.len() > max) {
+            fail(
+                format!(
+                    "expected no parser to be invoked more than {} times, but `{}` was invoked {} times",
+                    max,
+                    name,
+                    frames.len()
+                ),
+                frames,
+            );
+        }
+        self
+    }
+
+    /// Iterates over all the invocations, memoization hits included, depth-first.
+    fn invocations(&self) -> impl Iterator<Item = &Frame<'a>> {
+        self.roots
+            .iter()
+            .flat_map(|root| root.iter())
+            .filter(|frame| {
+                !matches!(
+                    frame.open.event,
+                    TraceEventType::MemoMiss | TraceEventType::Summary(_)
+                )
+            })
+    }
+}
+
+/// Assertions over the invocations of a single parser.
+pub struct ParserAssertions<'t, 'a> {
+    name: String,
+    frames: Vec<&'t Frame<'a>>,
+}
+
+impl ParserAssertions<'_, '_> {
+    /// Asserts that the parser was invoked at least once.
+    pub fn invoked(&self) -> &Self {
+        if self.frames.is_empty() {
+            fail(format!("expected `{}` to be invoked", self.name), &[]);
+        }
+        self
+    }
+
+    /// Asserts that the parser was invoked exactly `count` times.
+    pub fn invoked_times(&self, count: usize) -> &Self {
+        if self.frames.len() != count {
+            fail(
+                format!(
+                    "expected `{}` to be invoked {} times, but it was invoked {} times",
+                    self.name,
+                    count,
+                    self.frames.len()
+                ),
+                &self.frames,
+            );
+        }
+        self
+    }
+
+    /// Asserts that the parser was invoked at least once on an input starting with `prefix`.
+    pub fn invoked_with(&self, prefix: &str) -> &Self {
+        if !self
+            .frames
+            .iter()
+            .any(|frame| frame.open.input.starts_with(prefix))
+        {
+            fail(
+                format!(
+                    "expected `{}` to be invoked on input starting with {:?}",
+                    self.name, prefix
+                ),
+                &self.frames,
+            );
+        }
+        self
+    }
+
+    /// Asserts that the parser returned `Ok` exactly `count` times.
+    pub fn returned_ok(&self, count: usize) -> &Self {
+        self.returned(count, "Ok", |result| {
+            matches!(result, TraceEventType::CloseOk(_))
+        })
+    }
+
+    /// Asserts that the parser returned an `Error` exactly `count` times.
+    pub fn returned_error(&self, count: usize) -> &Self {
+        self.returned(count, "Error", |result| {
+            matches!(result, TraceEventType::CloseError(_))
+        })
+    }
+
+    /// Asserts that the parser returned a `Failure` exactly `count` times.
+    pub fn returned_failure(&self, count: usize) -> &Self {
+        self.returned(count, "Failure", |result| {
+            matches!(result, TraceEventType::CloseFailure(_))
+        })
+    }
+
+    /// Asserts that the parser never returned an `Error`, a `Failure`, or `Incomplete`.
+    pub fn never_failed(&self) -> &Self {
+        let failed: Vec<_> = self
+            .frames
+            .iter()
+            .copied()
+            .filter(|frame| frame.is_failure())
+            .collect();
+
+        if !failed.is_empty() {
+            fail(
+                format!(
+                    "expected `{}` to never fail, but it failed {} times",
+                    self.name,
+                    failed.len()
+                ),
+                &failed,
+            );
+        }
+        self
+    }
+
+    fn returned(&self, count: usize, kind: &str, filter: fn(&TraceEventType) -> bool) -> &Self {
+        let matching = self
+            .frames
+            .iter()
+            .filter(|frame| frame.result().is_some_and(filter))
+            .count();
+
+        if matching != count {
+            fail(
+                format!(
+                    "expected `{}` to return {} exactly {} times, but it did {} times",
+                    self.name, kind, count, matching
+                ),
+                &self.frames,
+            );
+        }
+        self
+    }
+}
+
+fn fail(message: String, frames: &[&Frame]) -> ! {
+    let mut report = message;
+
+    for frame in frames.iter().take(MAX_REPORTED_FRAMES) {
+        report.push('\n');
+        render(frame, frame.open.level, &mut report);
+    }
+    if frames.len() > MAX_REPORTED_FRAMES {
+        report.push_str(&format!(
+            "\n\n... and {} more invocations",
+            frames.len() - MAX_REPORTED_FRAMES
+        ));
+    }
+
+    panic!("{}", report)
+}
+
+/// Renders the events of a frame and its nested frames, indented relative to `base`.
+fn render(frame: &Frame, base: usize, output: &mut String) {
+    line(frame.open, base, output);
+    for child in frame.children.iter() {
+        render(child, base, output);
+    }
+    if let Some(close) = frame.close {
+        line(close, base, output);
+    }
+}
+
+fn line(event: &TraceEvent, base: usize, output: &mut String) {
+    output.push('\n');
+    output.push_str(&"| ".repeat(event.level.saturating_sub(base)));
+    output.push_str(&event.plain());
+}
+
+/// Runs assertions on the trace associated with a tag, see [assert_trace!](crate::assert_trace).
+///
+/// # Panics
+///
+/// Panics if there is no trace for the tag, or if an assertion fails.
+pub fn assert_trace_for_tag(tag: &str, assertions: impl FnOnce(&TraceAssertions)) {
+    crate::TRACE_TAGS.with(|tags| {
+        let tags = tags.borrow();
+        let trace = tags
+            .traces
+            .get(tag)
+            .unwrap_or_else(|| panic!("No trace found for tag '{}'", tag));
+        assertions(&TraceAssertions::new(trace));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, nom::error::VerboseError};
+
+    fn error() -> nom::IResult<&'static str, &'static str, VerboseError<&'static str>> {
+        Err(nom::Err::Error(VerboseError { errors: vec![] }))
+    }
+
+    fn value_trace() -> Trace {
+        let mut trace = Trace::default();
+        trace.open(Some("value"), "42, x", "value", false);
+        trace.open(None, "42, x", "number", false);
+        trace.close::<_, _, VerboseError<&str>>(None, "42, x", "number", &Ok((", x", "42")), false);
+        trace.close::<_, _, VerboseError<&str>>(
+            Some("value"),
+            "42, x",
+            "value",
+            &Ok((", x", "42")),
+            false,
+        );
+        trace.open(None, ", x", "ws", false);
+        trace.close::<_, _, VerboseError<&str>>(None, ", x", "ws", &Ok(("x", ", ")), false);
+        trace.open(Some("value"), "x", "value", false);
+        trace.open(None, "x", "number", false);
+        trace.close(None, "x", "number", &error(), false);
+        trace.close(Some("value"), "x", "value", &error(), false);
+        trace
+    }
+
+    fn panic_message(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+        let payload = std::panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn test_passing_assertions() {
+        let trace = value_trace();
+
+        TraceAssertions::new(&trace)
+            .max_invocations(2)
+            .parser("number")
+            .invoked()
+            .invoked_times(2)
+            .invoked_with("42")
+            .returned_ok(1)
+            .returned_error(1)
+            .returned_failure(0);
+        TraceAssertions::new(&trace)
+            .parser("value")
+            .invoked_times(2);
+        TraceAssertions::new(&trace).parser("ws").never_failed();
+    }
+
+    #[test]
+    fn test_failing_assertion_prints_subtree() {
+        let trace = value_trace();
+
+        let message = panic_message(|| {
+            TraceAssertions::new(&trace).parser("value").never_failed();
+        });
+        assert!(message.starts_with("expected `value` to never fail, but it failed 1 times"));
+        assert!(message.contains("\nvalue(\"x\")[value]\n| number(\"x\")\n| -> Error("));
+        assert!(!message.contains("42"));
+    }
+
+    #[test]
+    fn test_failing_max_invocations() {
+        let trace = value_trace();
+
+        let message = panic_message(|| {
+            TraceAssertions::new(&trace).max_invocations(1);
+        });
+        assert!(message.starts_with(
+            "expected no parser to be invoked more than 1 times, but `number` was invoked 2 times"
+        ));
+    }
+
+    #[test]
+    fn test_failing_invoked() {
+        let trace = value_trace();
+
+        let message = panic_message(|| {
+            TraceAssertions::new(&trace).parser("string").invoked();
+        });
+        assert_eq!(message, "expected `string` to be invoked");
+
+        let message = panic_message(|| {
+            TraceAssertions::new(&trace)
+                .parser("number")
+                .invoked_with("x1");
+        });
+        assert!(message.contains("number(\"42, x\")"));
+        assert!(message.contains("number(\"x\")"));
+    }
+}
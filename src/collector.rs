@@ -0,0 +1,181 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Collection of traces across threads.
+//!
+//! Traces are recorded in thread-local storage, which can't be reached from other threads.
+//! When a [TraceCollector] is installed, every top-level parse is moved out of the thread-local
+//! trace as soon as it returns, and forwarded to the collector along with its thread and its
+//! document ID. After a parallel job, all the parses are retrieved with a single call to
+//! [TraceCollector::take].
+//!
+//! Only the thread-local traces are collected: the traces owned by a
+//! [Tracer](crate::tracer::Tracer), a [TraceContext](crate::context::TraceContext) or a
+//! [TraceSession](crate::session::TraceSession) stay with their owner.
+//!
+//! ```
+//! use nom_tracer::{collector::{self, TraceCollector}, trace};
+//! use nom::{bytes::complete::tag, IResult};
+//!
+//! fn hello(input: &str) -> IResult<&str, &str> {
+//!     trace!(tag("hello"))(input)
+//! }
+//!
+//! let collector = TraceCollector::new();
+//! collector.install();
+//!
+//! std::thread::scope(|scope| {
+//!     for document in ["a.txt", "b.txt"] {
+//!         scope.spawn(move || collector::with_document(document, || hello("hello")));
+//!     }
+//! });
+//!
+//! TraceCollector::uninstall();
+//! assert_eq!(collector.take().len(), 2);
+//! ```
+
+use {
+    crate::{traces::Trace, TRACE_TAGS},
+    std::{
+        cell::RefCell,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+            Mutex,
+            RwLock,
+        },
+        thread::ThreadId,
+    },
+};
+
+static INSTALLED: RwLock<Option<TraceCollector>> = RwLock::new(None);
+
+/// Whether a collector is installed, so that parses don't take the lock when none is.
+static IS_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The ID of the document parsed by the current thread, if set.
+    static DOCUMENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// A top-level parse forwarded to a [TraceCollector].
+pub struct CollectedTrace {
+    /// The thread the parse ran on.
+    pub thread: ThreadId,
+    /// The name of the thread the parse ran on, if it has one.
+    pub thread_name: Option<String>,
+    /// The ID of the document being parsed, if set with [set_document] or [with_document].
+    pub document: Option<String>,
+    /// The tag of the trace the parse was recorded in.
+    pub tag: String,
+    /// The events of the parse.
+    pub trace: Trace,
+}
+
+/// A shareable collector of traces recorded on any thread.
+///
+/// Clones of a collector share the same storage.
+#[derive(Clone, Default)]
+pub struct TraceCollector {
+    traces: Arc<Mutex<Vec<CollectedTrace>>>,
+}
+
+impl TraceCollector {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Installs this collector for all threads, replacing any previously installed one.
+    pub fn install(&self) {
+        let mut installed = INSTALLED.write().unwrap_or_else(|e| e.into_inner());
+        *installed = Some(self.clone());
+        IS_INSTALLED.store(true, Ordering::Release);
+    }
+
+    /// Uninstalls the current collector, if any. Traces are kept in thread-local storage again.
+    pub fn uninstall() {
+        let mut installed = INSTALLED.write().unwrap_or_else(|e| e.into_inner());
+        *installed = None;
+        IS_INSTALLED.store(false, Ordering::Release);
+    }
+
+    /// Returns the number of parses collected so far.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no parse was collected so far.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Takes all the parses collected so far, leaving the collector empty.
+    pub fn take(&self) -> Vec<CollectedTrace> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Adds a parse to the collector.
+    pub fn push(&self, trace: CollectedTrace) {
+        self.lock().push(trace);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<CollectedTrace>> {
+        self.traces.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Sets the ID of the document parsed by the current thread, or clears it with `None`.
+pub fn set_document(document: Option<impl Into<String>>) {
+    DOCUMENT.with(|current| *current.borrow_mut() = document.map(Into::into));
+}
+
+/// Runs a function with the ID of the document parsed by the current thread set.
+///
+/// The previous document ID is restored afterward.
+pub fn with_document<R>(document: impl Into<String>, f: impl FnOnce() -> R) -> R {
+    let previous = DOCUMENT.with(|current| current.replace(Some(document.into())));
+    let result = f();
+    DOCUMENT.with(|current| current.replace(previous));
+    result
+}
+
+/// Forwards the events of a trace to the installed collector, if any, once its top-level
+/// parse has returned.
+pub(crate) fn forward(tag: &str, trace: &mut Trace) {
+    if trace.level != 0 || trace.events.is_empty() || !IS_INSTALLED.load(Ordering::Acquire) {
+        return;
+    }
+
+    let installed = INSTALLED.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(collector) = installed.as_ref() {
+        trace.unresolved = 0;
+        let thread = std::thread::current();
+        collector.push(CollectedTrace {
+            thread: thread.id(),
+            thread_name: thread.name().map(ToString::to_string),
+            document: DOCUMENT.with(|current| current.borrow().clone()),
+            tag: tag.to_string(),
+            trace: Trace {
+                events: trace.take_events(),
+                dropped: std::mem::take(&mut trace.dropped),
+                ..Trace::default()
+            },
+        });
+    }
+}
+
+/// Forwards the traces of the current thread to the installed collector, if any.
+///
+/// Top-level parses are forwarded as soon as they return, so this is only needed for parses
+/// that were recorded before the collector was installed.
+pub fn flush() {
+    TRACE_TAGS.with(|tags| {
+        let mut tags = tags.borrow_mut();
+        if tags.collect {
+            for (tag, trace) in tags.traces.iter_mut() {
+                forward(tag, trace);
+            }
+        }
+    });
+}
@@ -0,0 +1,395 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-tag trace settings.
+//!
+//! A [TraceConfig] gathers all the settings of a tag in a single value, which can be
+//! [applied](crate::tags::TraceTags::set_config), [read back](crate::tags::TraceTags::config),
+//! and [saved](crate::tags::TraceTags::save_config) then
+//! [restored](crate::tags::TraceTags::restore_config) with the settings of all the other tags.
+//!
+//! ```
+//! use nom_tracer::{config::{TraceCapture, TraceConfig}, tags::TraceTags};
+//!
+//! let mut tags = TraceTags::new();
+//! let saved = tags.save_config();
+//!
+//! tags.set_config("json.*", TraceConfig::new().capture(TraceCapture::Stats));
+//! assert_eq!(tags.config("json.string").capture, TraceCapture::Stats);
+//!
+//! tags.restore_config(saved);
+//! assert_eq!(tags.config("json.string").capture, TraceCapture::Events);
+//! ```
+
+use {
+    crate::{filters::TraceFilters, stats::TraceStats, traces::Trace},
+    std::{
+        collections::HashMap,
+        fmt::{Debug, Formatter},
+        io::Write,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// What a trace records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceCapture {
+    /// Records every event.
+    #[default]
+    Events,
+    /// Records per-location statistics only. See [TraceStats].
+    Stats,
+}
+
+impl TraceCapture {
+    /// Switches a trace to this capture. Switching to [TraceCapture::Events] drops its
+    /// statistics.
+    pub(crate) fn apply(self, trace: &mut Trace) {
+        match self {
+            TraceCapture::Events => trace.stats = None,
+            TraceCapture::Stats => {
+                trace.stats.get_or_insert_with(TraceStats::default);
+            }
+        }
+    }
+}
+
+/// Which events a trace keeps.
+///
+/// Bounded retention turns a trace into a flight recorder: a long-running parser keeps
+/// tracing in constant memory, and the last events are still there when something goes wrong.
+/// The number of events dropped is kept in [Trace::dropped], and shown in the rendered trace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceRetention {
+    /// Keeps every event.
+    #[default]
+    All,
+    /// Keeps the last events only.
+    LastEvents(usize),
+    /// Keeps the events of the last top-level invocations only.
+    LastInvocations(usize),
+    /// Keeps the events of the top-level invocations that didn't return `Ok` only.
+    ///
+    /// With real-time printing, the events of an invocation are printed once it failed.
+    FailuresOnly,
+}
+
+/// How the parsers silenced with [silence_tree!](crate::silence_tree!) show in a trace.
+///
+/// The events of a silenced parser are buffered in the silent trace until it returns, and
+/// thrown away once the trace recorded what it needs of them, whatever the mode.
+///
+/// This enum is only available when the `trace-silencing` feature is enabled.
+#[cfg(feature = "trace-silencing")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceSilence {
+    /// Records a single [Silenced](crate::events::TraceEventType::Silenced) event for each
+    /// silenced parser, with its input, its result, and the number of nested invocations
+    /// hidden.
+    #[default]
+    Summary,
+    /// Records nothing for the silenced parsers.
+    Hide,
+    /// Records the events of the silenced parsers that returned an `Error`, a `Failure` or
+    /// `Incomplete`, as if they weren't silenced, and a summary for the others.
+    ExpandOnFailure,
+}
+
+/// Where the events of a trace are printed in real-time.
+#[derive(Clone, Default)]
+pub enum TraceSink {
+    /// Prints to the standard output.
+    #[default]
+    Stdout,
+    /// Prints to the standard error.
+    Stderr,
+    /// Writes to a shared writer, like a log file.
+    Writer(Arc<Mutex<dyn Write + Send>>),
+}
+
+impl TraceSink {
+    /// Creates a sink writing to the given writer.
+    pub fn writer(writer: impl Write + Send + 'static) -> Self {
+        TraceSink::Writer(Arc::new(Mutex::new(writer)))
+    }
+
+    /// Writes a string to the sink, unbuffered.
+    pub fn write(&self, s: &str) {
+        match self {
+            TraceSink::Stdout => crate::print(s),
+            TraceSink::Stderr => {
+                let stderr = std::io::stderr();
+                let mut handle = stderr.lock();
+                write!(handle, "{}", s).unwrap();
+            }
+            TraceSink::Writer(writer) => {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                write!(writer, "{}", s).unwrap();
+                writer.flush().unwrap();
+            }
+        }
+    }
+}
+
+impl Debug for TraceSink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceSink::Stdout => write!(f, "Stdout"),
+            TraceSink::Stderr => write!(f, "Stderr"),
+            TraceSink::Writer(_) => write!(f, "Writer"),
+        }
+    }
+}
+
+impl PartialEq for TraceSink {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TraceSink::Stdout, TraceSink::Stdout) | (TraceSink::Stderr, TraceSink::Stderr) => true,
+            (TraceSink::Writer(a), TraceSink::Writer(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// All the settings of a tag.
+///
+/// The default configuration is the one of a newly created tag: active, not printed, without
+/// a maximum nesting level nor filters, and recording and keeping every event at every depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceConfig {
+    /// Whether the trace records events.
+    pub active: bool,
+    /// Whether the events are printed in real-time.
+    #[cfg(feature = "trace-print")]
+    pub print: bool,
+    /// Where the events are printed in real-time.
+    #[cfg(feature = "trace-print")]
+    pub sink: TraceSink,
+    /// The maximum nesting level before panicking, if set.
+    #[cfg(feature = "trace-max-level")]
+    pub max_level: Option<usize>,
+    /// What the trace records.
+    pub capture: TraceCapture,
+    /// Which events the trace keeps.
+    pub retention: TraceRetention,
+    /// The filters deciding which parsers are recorded.
+    pub filters: TraceFilters,
+    /// The deepest nesting level recorded, if set. See [Trace::record_depth].
+    pub record_depth: Option<usize>,
+    /// How the silenced parsers show in the trace.
+    #[cfg(feature = "trace-silencing")]
+    pub silence: TraceSilence,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            active: true,
+            #[cfg(feature = "trace-print")]
+            print: false,
+            #[cfg(feature = "trace-print")]
+            sink: TraceSink::default(),
+            #[cfg(feature = "trace-max-level")]
+            max_level: None,
+            capture: TraceCapture::default(),
+            retention: TraceRetention::default(),
+            filters: TraceFilters::default(),
+            record_depth: None,
+            #[cfg(feature = "trace-silencing")]
+            silence: TraceSilence::default(),
+        }
+    }
+}
+
+impl TraceConfig {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the trace records events.
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Sets whether the events are printed in real-time.
+    #[cfg(feature = "trace-print")]
+    pub fn print(mut self, print: bool) -> Self {
+        self.print = print;
+        self
+    }
+
+    /// Sets where the events are printed in real-time.
+    #[cfg(feature = "trace-print")]
+    pub fn sink(mut self, sink: TraceSink) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Sets the maximum nesting level before panicking, or removes it with `None`.
+    #[cfg(feature = "trace-max-level")]
+    pub fn max_level(mut self, max_level: Option<usize>) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Sets what the trace records.
+    pub fn capture(mut self, capture: TraceCapture) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Sets which events the trace keeps.
+    pub fn retention(mut self, retention: TraceRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Sets the filters deciding which parsers are recorded.
+    pub fn filters(mut self, filters: TraceFilters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Sets the deepest nesting level recorded, or records every level with `None`.
+    pub fn record_depth(mut self, record_depth: Option<usize>) -> Self {
+        self.record_depth = record_depth;
+        self
+    }
+
+    /// Sets how the silenced parsers show in the trace.
+    #[cfg(feature = "trace-silencing")]
+    pub fn silence(mut self, silence: TraceSilence) -> Self {
+        self.silence = silence;
+        self
+    }
+
+    /// Returns the configuration of a trace.
+    pub fn of(trace: &Trace) -> Self {
+        Self {
+            active: trace.active,
+            #[cfg(feature = "trace-print")]
+            print: trace.print,
+            #[cfg(feature = "trace-print")]
+            sink: trace.sink.clone(),
+            #[cfg(feature = "trace-max-level")]
+            max_level: trace.panic_on_level,
+            capture: match trace.stats {
+                Some(_) => TraceCapture::Stats,
+                None => TraceCapture::Events,
+            },
+            retention: trace.retention,
+            filters: trace.filters.clone(),
+            record_depth: trace.record_depth,
+            #[cfg(feature = "trace-silencing")]
+            silence: trace.silence,
+        }
+    }
+
+    /// Applies this configuration to a trace.
+    ///
+    /// Switching a trace to [TraceCapture::Events] drops its statistics, and a bounded
+    /// retention drops the events it doesn't keep.
+    pub fn apply(&self, trace: &mut Trace) {
+        trace.active = self.active;
+        if trace.retention != self.retention {
+            trace.set_retention(self.retention);
+        }
+        trace.filters = self.filters.clone();
+        trace.record_depth = self.record_depth;
+        #[cfg(feature = "trace-silencing")]
+        {
+            trace.silence = self.silence;
+        }
+        #[cfg(feature = "trace-print")]
+        {
+            trace.print = self.print;
+            trace.sink = self.sink.clone();
+        }
+        #[cfg(feature = "trace-max-level")]
+        {
+            trace.panic_on_level = self.max_level;
+        }
+        self.capture.apply(trace);
+    }
+}
+
+/// The settings of all the tags of a [TraceTags](crate::tags::TraceTags), as returned by
+/// [TraceTags::save_config](crate::tags::TraceTags::save_config).
+#[derive(Clone)]
+pub struct SavedConfig {
+    pub(crate) configs: HashMap<String, TraceConfig>,
+    pub(crate) rules: Vec<(String, crate::tags::Rule)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut trace = Trace::default();
+        assert_eq!(TraceConfig::of(&trace), TraceConfig::default());
+
+        let config = TraceConfig::new()
+            .active(false)
+            .capture(TraceCapture::Stats)
+            .record_depth(Some(2));
+        config.apply(&mut trace);
+        assert!(!trace.active);
+        assert!(trace.stats.is_some());
+        assert_eq!(TraceConfig::of(&trace), config);
+
+        TraceConfig::new().apply(&mut trace);
+        assert!(trace.active);
+        assert!(trace.stats.is_none());
+    }
+
+    #[test]
+    fn test_reapply_config_mid_parse() {
+        type Error<'a> = nom::error::VerboseError<&'a str>;
+
+        let mut trace = Trace::default();
+        let config = TraceConfig::new().retention(TraceRetention::FailuresOnly);
+        config.apply(&mut trace);
+
+        trace.open(None, "input", "outer", false);
+        trace.open(None, "input", "inner", false);
+        config.apply(&mut trace);
+        trace.close::<_, _, Error>(None, "input", "inner", &Ok(("", "input")), false);
+        trace.close::<_, _, Error>(None, "input", "outer", &Ok(("", "input")), false);
+
+        assert!(trace.events.is_empty());
+        assert_eq!(trace.dropped, 4);
+    }
+
+    #[cfg(feature = "trace-print")]
+    #[test]
+    fn test_writer_sink() {
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Shared::default();
+        let sink = TraceSink::writer(output.clone());
+        assert_eq!(sink, sink.clone());
+        assert_ne!(sink, TraceSink::Stdout);
+
+        let mut trace = Trace::default();
+        TraceConfig::new().print(true).sink(sink).apply(&mut trace);
+        trace.open(None, "input", "location", false);
+
+        let printed = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(printed.contains("location"));
+    }
+}
@@ -0,0 +1,489 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Explicit trace contexts.
+//!
+//! By default, traced parsers record into thread-local storage. This breaks when a single
+//! parse runs on several threads, like a streaming parse suspended on `Incomplete` in an async
+//! task and resumed on another worker thread: its events are split across unrelated traces,
+//! and the nesting levels get mixed up.
+//!
+//! A [TraceContext] carries its own tracing state. While it is [entered](TraceContext::enter),
+//! traced parsers record into it instead of the thread-local storage, whatever thread they run
+//! on. Futures can be [instrumented](TraceContext::instrument) to enter the context every time
+//! they are polled.
+
+#[cfg(feature = "trace-silencing")]
+use crate::{traces::Trace, TRACE_SILENT, TREE_SILENCE_LEVELS};
+use {
+    crate::{tags::TraceTags, TRACE_TAGS},
+    nom::IResult,
+    std::{
+        borrow::Cow,
+        cell::RefCell,
+        fmt::Debug,
+        future::Future,
+        ops::{Deref, DerefMut},
+        pin::Pin,
+        sync::{Arc, Mutex, MutexGuard},
+        task::{Context, Poll},
+    },
+};
+
+thread_local! {
+    /// The context entered by the current thread, if any.
+    static CURRENT: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+
+    /// The addresses of the states locked by the current thread.
+    static LOCKED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The tracing state owned by a [TraceContext].
+struct TraceState {
+    tags: TraceTags,
+    #[cfg(feature = "trace-silencing")]
+    silent: Trace,
+    #[cfg(feature = "trace-silencing")]
+    silence_levels: Vec<usize>,
+}
+
+impl TraceState {
+    fn new(tags: TraceTags) -> Self {
+        Self {
+            tags,
+            #[cfg(feature = "trace-silencing")]
+            silent: Trace::default(),
+            #[cfg(feature = "trace-silencing")]
+            silence_levels: Vec::new(),
+        }
+    }
+}
+
+/// A shareable handle to a tracing state, independent of the thread-local storage.
+///
+/// Clones of a context share the same state.
+///
+/// ```
+/// use nom_tracer::{context::TraceContext, trace, DEFAULT_TAG};
+/// use nom::{bytes::complete::tag, IResult};
+///
+/// fn hello(input: &str) -> IResult<&str, &str> {
+///     trace!(tag("hello"))(input)
+/// }
+///
+/// let context = TraceContext::new();
+/// let handle = context.clone();
+/// std::thread::spawn(move || handle.enter(|| hello("hello")))
+///     .join()
+///     .unwrap()
+///     .unwrap();
+///
+/// assert_eq!(context.with(|tags| tags.traces[DEFAULT_TAG].events.len()), 2);
+/// ```
+#[derive(Clone)]
+pub struct TraceContext {
+    state: Arc<Mutex<TraceState>>,
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TraceContext {
+    /// Creates a context with a fresh [TraceTags], as created by [TraceTags::new].
+    pub fn new() -> Self {
+        Self::with_tags(TraceTags::new())
+    }
+
+    /// Creates a context recording into the given [TraceTags].
+    pub fn with_tags(tags: TraceTags) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TraceState::new(tags))),
+        }
+    }
+
+    /// Returns the context entered by the current thread, if any.
+    pub fn current() -> Option<TraceContext> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Runs a function with this context entered: the parsers it runs record into this context.
+    ///
+    /// The previously entered context, if any, is restored afterward.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<TraceContext>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.replace(self.0.take()));
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// Wraps a future so that this context is entered every time it is polled.
+    pub fn instrument<F: Future>(&self, future: F) -> Instrumented<F> {
+        Instrumented {
+            future: Box::pin(future),
+            context: self.clone(),
+        }
+    }
+
+    /// Runs a function with the traces recorded so far in this context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the function runs a parser recording into this context, or accesses it again.
+    pub fn with<R>(&self, f: impl FnOnce(&TraceTags) -> R) -> R {
+        f(&self.lock().tags)
+    }
+
+    /// Runs a function with mutable access to the traces of this context, for example to
+    /// activate or reset some tags.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the function runs a parser recording into this context, or accesses it again.
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut TraceTags) -> R) -> R {
+        f(&mut self.lock().tags)
+    }
+
+    /// Locks the state of this context, panicking instead of deadlocking if the current thread
+    /// already holds it.
+    fn lock(&self) -> StateGuard<'_> {
+        let address = Arc::as_ptr(&self.state) as usize;
+        LOCKED.with(|locked| {
+            let mut locked = locked.borrow_mut();
+            assert!(
+                !locked.contains(&address),
+                "the trace context is already in use on this thread: parsers recording into it \
+                 can't run, and it can't be accessed, inside `TraceContext::with` or `with_mut`"
+            );
+            locked.push(address);
+        });
+
+        StateGuard {
+            guard: self.state.lock().unwrap_or_else(|e| e.into_inner()),
+            address,
+        }
+    }
+}
+
+/// The locked state of a [TraceContext].
+struct StateGuard<'a> {
+    guard: MutexGuard<'a, TraceState>,
+    address: usize,
+}
+
+impl Deref for StateGuard<'_> {
+    type Target = TraceState;
+
+    fn deref(&self) -> &TraceState {
+        &self.guard
+    }
+}
+
+impl DerefMut for StateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut TraceState {
+        &mut self.guard
+    }
+}
+
+impl Drop for StateGuard<'_> {
+    fn drop(&mut self) {
+        LOCKED.with(|locked| {
+            let mut locked = locked.borrow_mut();
+            if let Some(index) = locked.iter().rposition(|address| *address == self.address) {
+                locked.swap_remove(index);
+            }
+        });
+    }
+}
+
+/// A future running in a [TraceContext], created by [TraceContext::instrument].
+pub struct Instrumented<F> {
+    future: Pin<Box<F>>,
+    context: TraceContext,
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.context.enter(|| this.future.as_mut().poll(cx))
+    }
+}
+
+/// Mutable access to the tracing state parsers currently record into.
+pub(crate) struct Recorder<'a> {
+    tags: &'a mut TraceTags,
+    #[cfg(feature = "trace-silencing")]
+    silent: &'a mut Trace,
+    #[cfg(feature = "trace-silencing")]
+    silence_levels: &'a mut Vec<usize>,
+}
+
+/// Runs a function with the state of the current context, or the thread-local state if no
+/// context is entered.
+pub(crate) fn with_recorder<R>(f: impl FnOnce(Recorder) -> R) -> R {
+    if let Some(context) = TraceContext::current() {
+        let mut state = context.lock();
+        let state = &mut *state;
+
+        return f(Recorder {
+            tags: &mut state.tags,
+            #[cfg(feature = "trace-silencing")]
+            silent: &mut state.silent,
+            #[cfg(feature = "trace-silencing")]
+            silence_levels: &mut state.silence_levels,
+        });
+    }
+
+    TRACE_TAGS.with(|tags| {
+        #[cfg(feature = "trace-silencing")]
+        {
+            TRACE_SILENT.with(|silent| {
+                TREE_SILENCE_LEVELS.with(|silence_levels| {
+                    f(Recorder {
+                        tags: &mut tags.borrow_mut(),
+                        silent: &mut silent.borrow_mut(),
+                        silence_levels: &mut silence_levels.borrow_mut(),
+                    })
+                })
+            })
+        }
+
+        #[cfg(not(feature = "trace-silencing"))]
+        f(Recorder {
+            tags: &mut tags.borrow_mut(),
+        })
+    })
+}
+
+// The tags are borrowed as `Cow`s so that the traces they create keep static tags borrowed.
+#[allow(clippy::ptr_arg)]
+impl Recorder<'_> {
+    /// Records the opening of a parser.
+    pub(crate) fn open<I: AsRef<str>>(
+        self,
+        tag: &Cow<'static, str>,
+        context: Option<&'static str>,
+        input: I,
+        name: &'static str,
+    ) {
+        #[cfg(feature = "trace-silencing")]
+        if !self.silence_levels.is_empty() {
+            self.silent.open(context, input, name, true);
+            return;
+        }
+
+        self.tags.open(tag.clone(), context, input, name, false);
+    }
+
+    /// Records the closing of a parser.
+    pub(crate) fn close<I: AsRef<str>, O: Debug, E: Debug>(
+        self,
+        tag: &Cow<'static, str>,
+        context: Option<&'static str>,
+        input: I,
+        name: &'static str,
+        result: &IResult<I, O, E>,
+    ) {
+        #[cfg(feature = "trace-silencing")]
+        if !self.silence_levels.is_empty() {
+            self.silent.close(context, input, name, result, true);
+            return;
+        }
+
+        self.tags
+            .close(tag.clone(), context, input, name, result, false);
+    }
+
+    /// Records a memoization lookup.
+    #[cfg(feature = "trace-memo")]
+    pub(crate) fn memo<I: AsRef<str>, O: Debug, E: Debug>(
+        self,
+        tag: &Cow<'static, str>,
+        context: Option<&'static str>,
+        input: I,
+        name: &'static str,
+        cached: Option<&IResult<I, O, E>>,
+    ) {
+        #[cfg(feature = "trace-silencing")]
+        if !self.silence_levels.is_empty() {
+            self.silent.memo(context, input, name, cached, true);
+            return;
+        }
+
+        self.tags
+            .memo(tag.clone(), context, input, name, cached, false);
+    }
+
+    /// Records the opening of a silenced tree, and silences the parsers nested in it.
+    #[cfg(feature = "trace-silencing")]
+    pub(crate) fn open_silence<I: AsRef<str>>(
+        self,
+        tag: &Cow<'static, str>,
+        context: Option<&'static str>,
+        input: I,
+        name: &'static str,
+    ) {
+        // A top-level silenced tree starts from an empty buffer, so that it doesn't have to be
+        // looked for when it closes. This also drops the events of a tree that never closed.
+        if self.silence_levels.is_empty() {
+            self.silent.clear();
+        }
+
+        let cut_level = self.silent.level.max(self.tags.level_for_tag(tag));
+
+        self.silence_levels.push(cut_level);
+        self.silent.set_level(cut_level);
+        self.silent.open(context, input, name, true);
+    }
+
+    /// Records the closing of a silenced tree, and the silenced tree in the tagged trace if it
+    /// isn't nested in another one.
+    #[cfg(feature = "trace-silencing")]
+    pub(crate) fn close_silence<I: AsRef<str>, O: Debug, E: Debug>(
+        self,
+        tag: &Cow<'static, str>,
+        context: Option<&'static str>,
+        input: I,
+        name: &'static str,
+        result: &IResult<I, O, E>,
+    ) {
+        self.silent.close(context, input, name, result, true);
+        if self.silence_levels.pop().is_none() {
+            return;
+        }
+
+        if self.silence_levels.is_empty() {
+            self.tags
+                .silenced(tag, self.silent.events.make_contiguous());
+            // The tagged trace keeps what it needs of the events, so they can be thrown away.
+            self.silent.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{get_trace, reset_trace, trace, DEFAULT_TAG},
+        nom::{
+            bytes::streaming::tag,
+            error::{Error, ErrorKind},
+        },
+        std::task::{Wake, Waker},
+    };
+
+    /// A waker doing nothing, as the futures polled in the tests are polled again explicitly.
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn noop_waker() -> Waker {
+        Waker::from(Arc::new(NoopWaker))
+    }
+
+    fn hello(input: &str) -> IResult<&str, &str> {
+        trace!(tag("hello"))(input)
+    }
+
+    #[test]
+    fn test_enter_records_into_context() {
+        reset_trace!();
+        let context = TraceContext::new();
+
+        let _ = context.enter(|| hello("hello"));
+        assert!(TraceContext::current().is_none());
+        assert_eq!(get_trace!().unwrap(), "");
+        assert_eq!(
+            context.with(|tags| tags.traces[DEFAULT_TAG].events.len()),
+            2
+        );
+
+        context.with_mut(|tags| tags.clear(DEFAULT_TAG));
+        assert!(context.with(|tags| tags.traces[DEFAULT_TAG].events.is_empty()));
+    }
+
+    #[test]
+    #[should_panic(expected = "already in use")]
+    fn test_parse_inside_with_panics() {
+        let context = TraceContext::new();
+        context.enter(|| context.with(|_| hello("hello")).unwrap());
+    }
+
+    #[test]
+    fn test_context_usable_after_panic() {
+        let context = TraceContext::new();
+        let handle = context.clone();
+        let result = std::panic::catch_unwind(move || handle.with(|_| handle.with(|_| ())));
+        assert!(result.is_err());
+
+        let _ = context.enter(|| hello("hello"));
+        assert_eq!(
+            context.with(|tags| tags.traces[DEFAULT_TAG].events.len()),
+            2
+        );
+    }
+
+    #[test]
+    fn test_instrumented_future_across_threads() {
+        /// A future returning `Pending` once before parsing.
+        struct Parse {
+            polled: bool,
+        }
+
+        impl Future for Parse {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+                let res = trace!("outer", |i| {
+                    if self.polled {
+                        hello(i)
+                    } else {
+                        Err(nom::Err::Error(Error::new(i, ErrorKind::Tag)))
+                    }
+                })("hello");
+
+                if self.polled {
+                    assert!(res.is_ok());
+                    Poll::Ready(())
+                } else {
+                    self.polled = true;
+                    Poll::Pending
+                }
+            }
+        }
+
+        let context = TraceContext::new();
+        let mut future = context.instrument(Parse { polled: false });
+        assert!(Pin::new(&mut future)
+            .poll(&mut Context::from_waker(&noop_waker()))
+            .is_pending());
+
+        std::thread::spawn(move || {
+            assert!(Pin::new(&mut future)
+                .poll(&mut Context::from_waker(&noop_waker()))
+                .is_ready());
+        })
+        .join()
+        .unwrap();
+
+        context.with(|tags| {
+            let trace = &tags.traces[DEFAULT_TAG];
+            assert_eq!(trace.level, 0);
+            assert_eq!(trace.events.len(), 6);
+            assert_eq!(trace.events[3].level, 1);
+        });
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Grammar coverage reporting.
+//!
+//! Coverage records which traced parsers were invoked, and which of them ever succeeded or
+//! failed, across any number of parses. It can be built from existing [Trace]s, or collected
+//! globally for all the threads of the process between [start] and [stop], for example for
+//! a whole test suite.
+
+use {
+    crate::{events::TraceEventType, frames::Frame, traces::Trace},
+    nom::IResult,
+    std::{
+        collections::BTreeMap,
+        fmt::{Display, Formatter},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Mutex,
+        },
+    },
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static GLOBAL: Mutex<Coverage> = Mutex::new(Coverage::new());
+
+/// Identifies a traced parser: its location and optional context.
+pub type CoverageKey = (&'static str, Option<&'static str>);
+
+/// Invocation counters for a single traced parser.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CoverageEntry {
+    /// The number of times the parser was invoked.
+    pub invocations: usize,
+    /// The number of times the parser returned `Ok`.
+    pub ok: usize,
+    /// The number of times the parser returned an `Error`.
+    pub error: usize,
+    /// The number of times the parser returned a `Failure`.
+    pub failure: usize,
+    /// The number of times the parser returned `Incomplete`.
+    pub incomplete: usize,
+}
+
+impl CoverageEntry {
+    fn record(&mut self, result: &TraceEventType) {
+        self.invocations += 1;
+        match result {
+            TraceEventType::CloseOk(_) => self.ok += 1,
+            TraceEventType::CloseError(_) => self.error += 1,
+            TraceEventType::CloseFailure(_) => self.failure += 1,
+            TraceEventType::CloseIncomplete(_) => self.incomplete += 1,
+            TraceEventType::Open
+            | TraceEventType::MemoMiss
+            | TraceEventType::MemoHit(_)
+            | TraceEventType::Silenced { .. }
+            | TraceEventType::Summary(_) => {}
+        }
+    }
+
+    fn record_result<I, O, E>(&mut self, result: &IResult<I, O, E>) {
+        self.invocations += 1;
+        match result {
+            Ok(_) => self.ok += 1,
+            Err(nom::Err::Error(_)) => self.error += 1,
+            Err(nom::Err::Failure(_)) => self.failure += 1,
+            Err(nom::Err::Incomplete(_)) => self.incomplete += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &CoverageEntry) {
+        self.invocations += other.invocations;
+        self.ok += other.ok;
+        self.error += other.error;
+        self.failure += other.failure;
+        self.incomplete += other.incomplete;
+    }
+}
+
+/// Coverage of the traced parsers of a grammar.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    /// The counters of each traced parser, sorted by location and context.
+    pub entries: BTreeMap<CoverageKey, CoverageEntry>,
+}
+
+impl Coverage {
+    /// Creates an empty coverage.
+    pub const fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Builds the coverage of the invocations recorded in a trace.
+    pub fn from_trace(trace: &Trace) -> Self {
+        let mut coverage = Self::new();
+        coverage.record_trace(trace);
+        coverage
+    }
+
+    /// Adds the invocations recorded in a trace.
+    ///
+    /// Memoization hits count as invocations returning the cached result.
+    pub fn record_trace(&mut self, trace: &Trace) {
+        for root in Frame::from_events(&trace.events).iter() {
+            for frame in root.iter() {
+                if let Some(result) = frame.result() {
+                    self.record_event(frame.open.location, frame.open.context, result);
+                }
+            }
+        }
+    }
+
+    /// Adds a single invocation of a parser.
+    pub fn record<I, O, E>(
+        &mut self,
+        location: &'static str,
+        context: Option<&'static str>,
+        result: &IResult<I, O, E>,
+    ) {
+        self.entries
+            .entry((location, context))
+            .or_default()
+            .record_result(result);
+    }
+
+    fn record_event(
+        &mut self,
+        location: &'static str,
+        context: Option<&'static str>,
+        result: &TraceEventType,
+    ) {
+        self.entries
+            .entry((location, context))
+            .or_default()
+            .record(result);
+    }
+
+    /// Adds all the invocations of another coverage.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (key, entry) in other.entries.iter() {
+            self.entries.entry(*key).or_default().merge(entry);
+        }
+    }
+
+    /// Returns the parsers that were invoked but never returned `Ok`.
+    pub fn never_succeeded(&self) -> impl Iterator<Item = &CoverageKey> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.ok == 0)
+            .map(|(key, _)| key)
+    }
+
+    /// Returns the parsers that were invoked but never returned an `Error`, a `Failure`,
+    /// or `Incomplete`.
+    pub fn never_failed(&self) -> impl Iterator<Item = &CoverageKey> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.error + entry.failure + entry.incomplete == 0)
+            .map(|(key, _)| key)
+    }
+
+    /// Exports the coverage as CSV, with a header line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("location,context,invocations,ok,error,failure,incomplete\n");
+        for ((location, context), entry) in self.entries.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(location),
+                csv_field(context.unwrap_or_default()),
+                entry.invocations,
+                entry.ok,
+                entry.error,
+                entry.failure,
+                entry.incomplete
+            ));
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Display for Coverage {
+    /// Formats the coverage as a table, one line per traced parser.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let location_width = self
+            .entries
+            .keys()
+            .map(|(location, _)| location.len())
+            .chain(Some("location".len()))
+            .max()
+            .unwrap_or_default();
+        let context_width = self
+            .entries
+            .keys()
+            .map(|(_, context)| context.unwrap_or_default().len())
+            .chain(Some("context".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<location_width$}  {:<context_width$}  {:>11}  {:>6}  {:>6}  {:>7}  {:>10}",
+            "location", "context", "invocations", "ok", "error", "failure", "incomplete"
+        )?;
+
+        for ((location, context), entry) in self.entries.iter() {
+            writeln!(
+                f,
+                "{:<location_width$}  {:<context_width$}  {:>11}  {:>6}  {:>6}  {:>7}  {:>10}",
+                location,
+                context.unwrap_or_default(),
+                entry.invocations,
+                entry.ok,
+                entry.error,
+                entry.failure,
+                entry.incomplete
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts collecting the coverage of all traced parsers, in all threads.
+pub fn start() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stops collecting coverage. The coverage collected so far is kept.
+pub fn stop() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Returns `true` if coverage is being collected.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns a copy of the coverage collected so far.
+pub fn global() -> Coverage {
+    GLOBAL.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Clears the coverage collected so far.
+pub fn reset() {
+    *GLOBAL.lock().unwrap_or_else(|e| e.into_inner()) = Coverage::new();
+}
+
+/// Adds a single invocation of a parser to the global coverage, if it is being collected.
+pub(crate) fn record_global<I, O, E>(
+    location: &'static str,
+    context: Option<&'static str>,
+    result: &IResult<I, O, E>,
+) {
+    if is_enabled() {
+        GLOBAL
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(location, context, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, nom::error::VerboseError};
+
+    #[test]
+    fn test_coverage_from_trace() {
+        let mut trace = Trace::default();
+        trace.open(None, "ab", "outer", false);
+        trace.open(Some("a"), "ab", "inner", false);
+        trace.close::<_, _, VerboseError<&str>>(Some("a"), "ab", "inner", &Ok(("b", "a")), false);
+        trace.close::<_, &str, _>(
+            None,
+            "ab",
+            "outer",
+            &Err(nom::Err::Error(VerboseError::<&str> { errors: vec![] })),
+            false,
+        );
+
+        let coverage = Coverage::from_trace(&trace);
+        assert_eq!(coverage.entries.len(), 2);
+        assert_eq!(
+            coverage.entries[&("inner", Some("a"))],
+            CoverageEntry {
+                invocations: 1,
+                ok: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            coverage.entries[&("outer", None)],
+            CoverageEntry {
+                invocations: 1,
+                error: 1,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            coverage.never_succeeded().collect::<Vec<_>>(),
+            vec![&("outer", None)]
+        );
+        assert_eq!(
+            coverage.never_failed().collect::<Vec<_>>(),
+            vec![&("inner", Some("a"))]
+        );
+    }
+
+    #[test]
+    fn test_coverage_merge_and_export() {
+        let mut coverage = Coverage::new();
+        coverage.record::<_, _, ()>("number", None, &Ok(("", "1")));
+
+        let mut other = Coverage::new();
+        other.record::<&str, &str, _>("number", None, &Err(nom::Err::Failure(())));
+        other.record::<_, _, ()>("string", Some("quoted, string"), &Ok(("", "s")));
+
+        coverage.merge(&other);
+        assert_eq!(coverage.entries[&("number", None)].invocations, 2);
+        assert_eq!(coverage.entries[&("number", None)].failure, 1);
+
+        assert_eq!(
+            coverage.to_csv(),
+            "location,context,invocations,ok,error,failure,incomplete\n\
+             number,,2,1,0,1,0\n\
+             string,\"quoted, string\",1,1,0,0,0\n"
+        );
+
+        let table = coverage.to_string();
+        assert!(table.starts_with("location"));
+        assert_eq!(table.lines().count(), 3);
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+use {
+    crate::{failure::FurthestFailure, frames::Frame, traces::Trace},
+    miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode},
+    std::{
+        error::Error,
+        fmt::{Debug, Display, Formatter},
+    },
+};
+
+/// A [miette] diagnostic describing a failed traced parse.
+///
+/// The primary label points at the [furthest failure](FurthestFailure) in the input, and a
+/// secondary label spans the input consumed by each enclosing parser that has a context,
+/// from where it started up to the failure.
+pub struct TraceDiagnostic {
+    /// The furthest failure of the parse.
+    pub failure: FurthestFailure,
+    /// The contexts of the parsers enclosing the failure, from the outermost to the
+    /// innermost, with the offset where each of them started.
+    pub contexts: Vec<(&'static str, usize)>,
+    source: NamedSource<String>,
+}
+
+impl TraceDiagnostic {
+    /// Builds a diagnostic from the trace of a failed parse.
+    ///
+    /// Like [FurthestFailure::from_trace], this expects the trace to hold a single parse of
+    /// `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace` - The trace of the failed parse.
+    /// * `name` - The name of the source, usually a file name, shown in the rendered diagnostic.
+    /// * `source` - The complete input given to the parser.
+    ///
+    /// # Returns
+    ///
+    /// The diagnostic, or `None` if no leaf parser failed in the trace.
+    pub fn from_trace(
+        trace: &Trace,
+        name: impl AsRef<str>,
+        source: impl Into<String>,
+    ) -> Option<Self> {
+        let source = source.into();
+        let failure = FurthestFailure::from_trace(trace, &source)?;
+        let remaining = source.len() - failure.offset;
+
+        let frames = Frame::from_events(&trace.events);
+        let mut path = Vec::new();
+        for frame in frames.iter() {
+            if failure_path(frame, remaining, &mut path) {
+                break;
+            }
+        }
+
+        let contexts =
+            path.iter()
+                .take(path.len().saturating_sub(1))
+                .filter_map(|frame| {
+                    frame.open.context.map(|context| {
+                        (context, source.len().saturating_sub(frame.open.input.len()))
+                    })
+                })
+                .collect();
+
+        Some(Self {
+            failure,
+            contexts,
+            source: NamedSource::new(name, source),
+        })
+    }
+}
+
+/// Fills `path` with the frames leading to the first failing leaf starting at `remaining`.
+fn failure_path<'f, 'a>(
+    frame: &'f Frame<'a>,
+    remaining: usize,
+    path: &mut Vec<&'f Frame<'a>>,
+) -> bool {
+    path.push(frame);
+
+    if frame.is_leaf() {
+        if frame.is_failure() && frame.open.input.len() == remaining {
+            return true;
+        }
+    } else {
+        for child in frame.children.iter() {
+            if failure_path(child, remaining, path) {
+                return true;
+            }
+        }
+    }
+
+    path.pop();
+    false
+}
+
+impl Display for TraceDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.failure.expected.as_slice() {
+            [] => write!(f, "parsing failed"),
+            [name] => write!(f, "expected `{}`", name),
+            names => {
+                write!(f, "expected one of ")?;
+                for (i, name) in names.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{}`", name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Debug for TraceDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceDiagnostic")
+            .field("failure", &self.failure)
+            .field("contexts", &self.contexts)
+            .finish()
+    }
+}
+
+impl Error for TraceDiagnostic {}
+
+impl Diagnostic for TraceDiagnostic {
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.source)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let offset = self.failure.offset;
+        let len = self
+            .source
+            .inner()
+            .get(offset..)
+            .and_then(|rest| rest.chars().next())
+            .map(char::len_utf8)
+            .unwrap_or(0);
+
+        let primary = LabeledSpan::new_primary_with_span(Some("here".to_string()), (offset, len));
+        let secondary = self.contexts.iter().map(move |(context, start)| {
+            LabeledSpan::at(*start..offset, format!("in {}", context))
+        });
+
+        Some(Box::new(std::iter::once(primary).chain(secondary)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{reset_trace, tr, DEFAULT_TAG, TRACE_TAGS},
+        nom::{
+            branch::alt,
+            bytes::complete::tag,
+            character::complete::digit1,
+            error::VerboseError,
+            multi::separated_list0,
+            sequence::delimited,
+            IResult,
+        },
+    };
+
+    fn array(input: &str) -> IResult<&str, Vec<&str>, VerboseError<&str>> {
+        tr(
+            DEFAULT_TAG,
+            Some("array"),
+            "array",
+            delimited(
+                tag("["),
+                separated_list0(
+                    tag(", "),
+                    alt((
+                        tr(DEFAULT_TAG, Some("number"), "number", digit1),
+                        tr(DEFAULT_TAG, Some("string"), "string", tag("\"\"")),
+                    )),
+                ),
+                tag("]"),
+            ),
+        )(input)
+    }
+
+    #[test]
+    fn test_trace_diagnostic() {
+        reset_trace!();
+
+        let source = "[1, x]";
+        assert!(array(source).is_err());
+
+        let diagnostic = TRACE_TAGS
+            .with(|tags| {
+                TraceDiagnostic::from_trace(&tags.borrow().traces[DEFAULT_TAG], "input.txt", source)
+            })
+            .unwrap();
+
+        assert_eq!(diagnostic.to_string(), "expected one of `number`, `string`");
+        assert_eq!(diagnostic.contexts, vec![("array", 0)]);
+
+        let labels: Vec<_> = diagnostic.labels().unwrap().collect();
+        assert_eq!(labels.len(), 2);
+        assert!(labels[0].primary());
+        assert_eq!((labels[0].offset(), labels[0].len()), (4, 1));
+        assert_eq!(labels[1].label(), Some("in array"));
+        assert_eq!((labels[1].offset(), labels[1].len()), (0, 4));
+    }
+}
@@ -0,0 +1,392 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+use {
+    crate::{
+        events::{TraceEvent, TraceEventType},
+        traces::Trace,
+    },
+    std::{
+        borrow::Cow,
+        collections::VecDeque,
+        fmt::{Display, Formatter},
+    },
+};
+
+/// Above this number of cells, the alignment falls back to replacing the whole middle part.
+const MAX_ALIGNMENT_CELLS: usize = 4_000_000;
+/// The number of unchanged events shown around each change in the rendered view.
+const CONTEXT_LINES: usize = 3;
+
+/// How two traces first diverge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DivergenceKind {
+    /// A different parser was invoked, or a parser was invoked in only one of the traces.
+    Parser,
+    /// The same parser returned a different result.
+    Result,
+    /// The same parser was invoked after a different amount of input was consumed.
+    Consumption,
+}
+
+/// The first point where two traces diverge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Divergence {
+    /// The index of the first diverging event, in both traces.
+    pub index: usize,
+    /// How the traces diverge at this event.
+    pub kind: DivergenceKind,
+}
+
+/// A step of the alignment of two event sequences.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiffOp {
+    /// The same parser event, at the given indexes of the left and right traces.
+    Equal(usize, usize),
+    /// An event only in the left trace.
+    Removed(usize),
+    /// An event only in the right trace.
+    Added(usize),
+}
+
+/// Compares the events of two traces.
+///
+/// Events are aligned by nesting level, location, context and event type (including the
+/// result), ignoring the input, so traces of two different inputs can be compared. The
+/// amount of input consumed before each event, relative to the first event of each trace,
+/// is used to detect consumption differences.
+pub struct TraceDiff<'a> {
+    left: Cow<'a, [TraceEvent]>,
+    right: Cow<'a, [TraceEvent]>,
+    /// The first divergence between the traces, or `None` if they are equivalent.
+    pub divergence: Option<Divergence>,
+    /// The alignment of the events of both traces.
+    pub ops: Vec<DiffOp>,
+}
+
+impl<'a> TraceDiff<'a> {
+    /// Compares two traces, for example before and after a grammar change.
+    pub fn new(left: &'a Trace, right: &'a Trace) -> Self {
+        Self::compare(contiguous(&left.events), contiguous(&right.events))
+    }
+
+    /// Compares two sequences of events.
+    pub fn from_events(left: &'a [TraceEvent], right: &'a [TraceEvent]) -> Self {
+        Self::compare(Cow::Borrowed(left), Cow::Borrowed(right))
+    }
+
+    fn compare(left: Cow<'a, [TraceEvent]>, right: Cow<'a, [TraceEvent]>) -> Self {
+        let mut diff = Self {
+            left,
+            right,
+            divergence: None,
+            ops: Vec::new(),
+        };
+        diff.divergence = diff.first_divergence();
+        diff.ops = diff.align();
+        diff
+    }
+
+    fn first_divergence(&self) -> Option<Divergence> {
+        let len = self.left.len().max(self.right.len());
+
+        (0..len).find_map(|index| {
+            let kind = match (self.left.get(index), self.right.get(index)) {
+                (Some(l), Some(r)) if !same_parser(l, r) => DivergenceKind::Parser,
+                (Some(l), Some(r)) if l.event != r.event => DivergenceKind::Result,
+                (Some(_), Some(_)) if !self.same_consumption(index, index) => {
+                    DivergenceKind::Consumption
+                }
+                (Some(_), Some(_)) => return None,
+                _ => DivergenceKind::Parser,
+            };
+            Some(Divergence { index, kind })
+        })
+    }
+
+    fn same_consumption(&self, left: usize, right: usize) -> bool {
+        consumed(&self.left, left) == consumed(&self.right, right)
+    }
+
+    fn align(&self) -> Vec<DiffOp> {
+        let (n, m) = (self.left.len(), self.right.len());
+
+        let prefix = self
+            .left
+            .iter()
+            .zip(self.right.iter())
+            .take_while(|(l, r)| same_event(l, r))
+            .count();
+        let suffix = self.left[prefix..]
+            .iter()
+            .rev()
+            .zip(self.right[prefix..].iter().rev())
+            .take_while(|(l, r)| same_event(l, r))
+            .count();
+
+        let mut ops: Vec<_> = (0..prefix).map(|i| DiffOp::Equal(i, i)).collect();
+
+        let left = &self.left[prefix..n - suffix];
+        let right = &self.right[prefix..m - suffix];
+
+        if left.len() * right.len() <= MAX_ALIGNMENT_CELLS {
+            // Longest common subsequence, computed from the end so it can be walked forward.
+            let width = right.len() + 1;
+            let mut lcs = vec![0u32; (left.len() + 1) * width];
+            for i in (0..left.len()).rev() {
+                for j in (0..right.len()).rev() {
+                    lcs[i * width + j] = if same_event(&left[i], &right[j]) {
+                        lcs[(i + 1) * width + j + 1] + 1
+                    } else {
+                        lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                    };
+                }
+            }
+
+            let (mut i, mut j) = (0, 0);
+            while i < left.len() && j < right.len() {
+                if same_event(&left[i], &right[j]) {
+                    ops.push(DiffOp::Equal(prefix + i, prefix + j));
+                    i += 1;
+                    j += 1;
+                } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+                    ops.push(DiffOp::Removed(prefix + i));
+                    i += 1;
+                } else {
+                    ops.push(DiffOp::Added(prefix + j));
+                    j += 1;
+                }
+            }
+            ops.extend((i..left.len()).map(|i| DiffOp::Removed(prefix + i)));
+            ops.extend((j..right.len()).map(|j| DiffOp::Added(prefix + j)));
+        } else {
+            ops.extend((0..left.len()).map(|i| DiffOp::Removed(prefix + i)));
+            ops.extend((0..right.len()).map(|j| DiffOp::Added(prefix + j)));
+        }
+
+        ops.extend((0..suffix).map(|k| DiffOp::Equal(n - suffix + k, m - suffix + k)));
+        ops
+    }
+
+    fn is_change(&self, op: &DiffOp) -> bool {
+        match op {
+            DiffOp::Equal(l, r) => !self.same_consumption(*l, *r),
+            DiffOp::Removed(_) | DiffOp::Added(_) => true,
+        }
+    }
+}
+
+fn same_parser(l: &TraceEvent, r: &TraceEvent) -> bool {
+    l.level == r.level
+        && l.location == r.location
+        && l.context == r.context
+        && event_kind(&l.event) == event_kind(&r.event)
+}
+
+/// Groups event types so that closes with different results are the same kind of event.
+fn event_kind(event: &TraceEventType) -> u8 {
+    match event {
+        TraceEventType::Open => 0,
+        TraceEventType::CloseOk(_)
+        | TraceEventType::CloseError(_)
+        | TraceEventType::CloseFailure(_)
+        | TraceEventType::CloseIncomplete(_) => 1,
+        TraceEventType::MemoMiss => 2,
+        TraceEventType::MemoHit(_) => 3,
+        TraceEventType::Summary(_) => 4,
+        TraceEventType::Silenced { .. } => 5,
+    }
+}
+
+/// Borrows the events of a trace as a slice, copying them if they wrap around in the queue.
+fn contiguous(events: &VecDeque<TraceEvent>) -> Cow<'_, [TraceEvent]> {
+    match events.as_slices() {
+        (front, []) => Cow::Borrowed(front),
+        _ => Cow::Owned(events.iter().cloned().collect()),
+    }
+}
+
+fn same_event(l: &TraceEvent, r: &TraceEvent) -> bool {
+    same_parser(l, r) && l.event == r.event
+}
+
+fn consumed(events: &[TraceEvent], index: usize) -> usize {
+    let start = events.first().map(|e| e.input.len()).unwrap_or_default();
+    start.saturating_sub(events[index].input.len())
+}
+
+fn line(event: &TraceEvent) -> String {
+    format!("{}{}", "| ".repeat(event.level), event.plain())
+}
+
+impl Display for TraceDiff<'_> {
+    /// Formats the first divergence, followed by the changed events with some
+    /// unchanged events around them, in a unified diff style.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Some(divergence) = self.divergence else {
+            return writeln!(f, "traces are identical");
+        };
+
+        let kind = match divergence.kind {
+            DivergenceKind::Parser => "different parser invoked",
+            DivergenceKind::Result => "different result",
+            DivergenceKind::Consumption => "different consumption",
+        };
+        writeln!(
+            f,
+            "first divergence at event {}: {}",
+            divergence.index, kind
+        )?;
+
+        let mut visible = vec![false; self.ops.len()];
+        for (i, op) in self.ops.iter().enumerate() {
+            if self.is_change(op) {
+                let end = (i + CONTEXT_LINES + 1).min(self.ops.len());
+                visible[i.saturating_sub(CONTEXT_LINES)..end].fill(true);
+            }
+        }
+
+        for (i, op) in self.ops.iter().enumerate() {
+            if !visible[i] {
+                continue;
+            }
+            if i == 0 || !visible[i - 1] {
+                writeln!(f, "@@ event {} @@", i)?;
+            }
+
+            match *op {
+                DiffOp::Equal(l, r) if self.same_consumption(l, r) => {
+                    writeln!(f, "  {}", line(&self.left[l]))?
+                }
+                DiffOp::Equal(l, r) => {
+                    writeln!(f, "- {}", line(&self.left[l]))?;
+                    writeln!(f, "+ {}", line(&self.right[r]))?
+                }
+                DiffOp::Removed(l) => writeln!(f, "- {}", line(&self.left[l]))?,
+                DiffOp::Added(r) => writeln!(f, "+ {}", line(&self.right[r]))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, nom::error::VerboseError};
+
+    fn trace(inner: &'static str, input: &'static str, ok: bool) -> Trace {
+        let mut trace = Trace::default();
+        trace.open(None, input, "outer", false);
+        trace.open(None, input, "first", false);
+        trace.close::<_, _, VerboseError<&str>>(None, input, "first", &Ok(("", "a")), false);
+        trace.open(None, &input[1..], inner, false);
+        let result: nom::IResult<&str, &str, VerboseError<&str>> = if ok {
+            Ok(("", "b"))
+        } else {
+            Err(nom::Err::Error(VerboseError { errors: vec![] }))
+        };
+        trace.close(None, &input[1..], inner, &result, false);
+        trace.close(None, input, "outer", &result, false);
+        trace
+    }
+
+    #[test]
+    fn test_wrapped_events() {
+        let left = trace("second", "ab", true);
+        let mut right = trace("second", "ab", true);
+        let events = std::mem::take(&mut right.events);
+        right.events = VecDeque::with_capacity(events.len());
+        right.events.push_back(events[0].clone());
+        right.events.pop_front();
+        right.events.extend(events);
+        assert!(!right.events.as_slices().1.is_empty());
+
+        let diff = TraceDiff::new(&left, &right);
+        assert_eq!(diff.divergence, None);
+    }
+
+    #[test]
+    fn test_identical() {
+        let (left, right) = (trace("second", "ab", true), trace("second", "ab", true));
+        let diff = TraceDiff::new(&left, &right);
+
+        assert_eq!(diff.divergence, None);
+        assert!(diff.ops.iter().all(|op| matches!(op, DiffOp::Equal(_, _))));
+        assert_eq!(diff.to_string(), "traces are identical\n");
+    }
+
+    #[test]
+    fn test_different_parser() {
+        let (left, right) = (trace("second", "ab", true), trace("other", "ab", true));
+        let diff = TraceDiff::new(&left, &right);
+
+        assert_eq!(
+            diff.divergence,
+            Some(Divergence {
+                index: 3,
+                kind: DivergenceKind::Parser
+            })
+        );
+        assert!(diff.ops.contains(&DiffOp::Removed(3)));
+        assert!(diff.ops.contains(&DiffOp::Added(3)));
+
+        let rendered = diff.to_string();
+        assert!(rendered.starts_with("first divergence at event 3: different parser invoked"));
+        assert!(rendered.contains("- | second(\"b\")"));
+        assert!(rendered.contains("+ | other(\"b\")"));
+    }
+
+    #[test]
+    fn test_different_result() {
+        let (left, right) = (trace("second", "ab", true), trace("second", "ab", false));
+        let diff = TraceDiff::new(&left, &right);
+
+        assert_eq!(
+            diff.divergence,
+            Some(Divergence {
+                index: 4,
+                kind: DivergenceKind::Result
+            })
+        );
+    }
+
+    #[test]
+    fn test_different_consumption() {
+        let mut right = trace("second", "ab", true);
+        right.events[3].input = "".to_string();
+        let left = trace("second", "ab", true);
+        let diff = TraceDiff::new(&left, &right);
+
+        assert_eq!(
+            diff.divergence,
+            Some(Divergence {
+                index: 3,
+                kind: DivergenceKind::Consumption
+            })
+        );
+        assert!(diff.to_string().contains("+ | second(\"\")"));
+    }
+
+    #[test]
+    fn test_different_length() {
+        let left = trace("second", "ab", true);
+        let mut right = trace("second", "ab", true);
+        right.events.truncate(2);
+        let diff = TraceDiff::new(&left, &right);
+
+        assert_eq!(
+            diff.divergence,
+            Some(Divergence {
+                index: 2,
+                kind: DivergenceKind::Parser
+            })
+        );
+        assert_eq!(
+            diff.ops
+                .iter()
+                .filter(|op| matches!(op, DiffOp::Removed(_)))
+                .count(),
+            4
+        );
+    }
+}
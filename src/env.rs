@@ -0,0 +1,197 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trace configuration from the environment.
+//!
+//! The thread-local [TraceTags](crate::tags::TraceTags) are configured from the [TRACE_ENV]
+//! environment variable when they are first used, so tracing can be tuned for a deployed
+//! binary without changing its code. The variable holds comma-separated directives:
+//!
+//! - `tag`: activates the tag.
+//! - `-tag`: deactivates the tag.
+//! - `tag:option:option...`: activates the tag with some options:
+//!   - `print`: prints its events in real-time (requires the `trace-print` feature).
+//!   - `max=N`: panics above the nesting level `N` (requires the `trace-max-level` feature).
+//!   - `format=tree` or `format=stats`: records the events, or only statistics.
+//!
+//! Tags can be patterns, like `json.*` (see [TraceTags](crate::tags::TraceTags)). Directives are
+//! applied in order, so `NOM_TRACE=-*,json.*:print` only traces the `json` tags, and prints
+//! them.
+
+use {
+    crate::config::TraceCapture,
+    std::{
+        error::Error,
+        fmt::{Display, Formatter},
+    },
+};
+
+/// The environment variable configuring the thread-local traces.
+pub const TRACE_ENV: &str = "NOM_TRACE";
+
+/// A single directive of a trace specification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceDirective {
+    /// The tag, or the pattern, the directive applies to.
+    pub tag: String,
+    /// Whether the tag is activated or deactivated.
+    pub active: bool,
+    /// Whether the events are printed in real-time, if set.
+    pub print: Option<bool>,
+    /// The maximum nesting level, if set.
+    pub max_level: Option<usize>,
+    /// What the trace records, if set.
+    pub format: Option<TraceCapture>,
+}
+
+/// An invalid directive in a trace specification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceSpecError {
+    /// The invalid directive.
+    pub directive: String,
+    /// Why the directive is invalid.
+    pub reason: String,
+}
+
+impl Display for TraceSpecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid trace directive \"{}\": {}",
+            self.directive, self.reason
+        )
+    }
+}
+
+impl Error for TraceSpecError {}
+
+/// Parses a trace specification, like `json:print,http:max=64,-default`.
+///
+/// Empty directives are ignored. See the [module documentation](self) for the syntax.
+pub fn parse(spec: &str) -> Result<Vec<TraceDirective>, TraceSpecError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(parse_directive)
+        .collect()
+}
+
+fn parse_directive(directive: &str) -> Result<TraceDirective, TraceSpecError> {
+    let error = |reason: String| TraceSpecError {
+        directive: directive.to_string(),
+        reason,
+    };
+
+    let mut parts = directive.split(':');
+    let tag = parts.next().unwrap_or_default();
+    let (tag, active) = match tag.strip_prefix('-') {
+        Some(tag) => (tag, false),
+        None => (tag, true),
+    };
+    if tag.is_empty() {
+        return Err(error("missing tag".to_string()));
+    }
+
+    let mut parsed = TraceDirective {
+        tag: tag.to_string(),
+        active,
+        print: None,
+        max_level: None,
+        format: None,
+    };
+
+    for option in parts {
+        if !active {
+            return Err(error("a deactivated tag takes no options".to_string()));
+        }
+
+        match option.split_once('=') {
+            None if option == "print" => parsed.print = Some(true),
+            Some(("max", level)) => {
+                parsed.max_level = Some(
+                    level
+                        .parse()
+                        .map_err(|_| error(format!("invalid max level \"{}\"", level)))?,
+                )
+            }
+            Some(("format", "tree")) => parsed.format = Some(TraceCapture::Events),
+            Some(("format", "stats")) => parsed.format = Some(TraceCapture::Stats),
+            Some(("format", format)) => {
+                return Err(error(format!("unknown format \"{}\"", format)))
+            }
+            _ => return Err(error(format!("unknown option \"{}\"", option))),
+        }
+    }
+
+    #[cfg(not(feature = "trace-print"))]
+    if parsed.print.is_some() {
+        return Err(error(
+            "`print` requires the trace-print feature".to_string(),
+        ));
+    }
+    #[cfg(not(feature = "trace-max-level"))]
+    if parsed.max_level.is_some() {
+        return Err(error(
+            "`max` requires the trace-max-level feature".to_string(),
+        ));
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "trace-print", feature = "trace-max-level"))]
+    #[test]
+    fn test_parse() {
+        let directives = parse("json:print, http:max=64:format=stats,,-default").unwrap();
+        assert_eq!(
+            directives,
+            vec![
+                TraceDirective {
+                    tag: "json".to_string(),
+                    active: true,
+                    print: Some(true),
+                    max_level: None,
+                    format: None,
+                },
+                TraceDirective {
+                    tag: "http".to_string(),
+                    active: true,
+                    print: None,
+                    max_level: Some(64),
+                    format: Some(TraceCapture::Stats),
+                },
+                TraceDirective {
+                    tag: "default".to_string(),
+                    active: false,
+                    print: None,
+                    max_level: None,
+                    format: None,
+                },
+            ]
+        );
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (spec, reason) in [
+            ("-", "missing tag"),
+            (":print", "missing tag"),
+            ("-json:print", "a deactivated tag takes no options"),
+            ("json:max=many", "invalid max level \"many\""),
+            ("json:format=xml", "unknown format \"xml\""),
+            ("json:verbose", "unknown option \"verbose\""),
+        ] {
+            assert_eq!(parse(spec).unwrap_err().reason, reason, "{}", spec);
+        }
+
+        assert_eq!(
+            parse("a,b:oops").unwrap_err().to_string(),
+            "invalid trace directive \"b:oops\": unknown option \"oops\""
+        );
+    }
+}
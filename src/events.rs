@@ -8,7 +8,8 @@ use std::fmt::{Display, Formatter};
 /// Represents the type of a trace event.
 ///
 /// This enum is used to categorize different stages or outcomes of a parsing operation.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum TraceEventType {
     /// Indicates the start of a parsing operation.
     Open,
@@ -20,6 +21,107 @@ pub enum TraceEventType {
     CloseFailure(String),
     /// Indicates an incomplete parse, containing the additional data needed.
     CloseIncomplete(nom::Needed),
+    /// Indicates that a memoized parser had no cached result and is about to run.
+    MemoMiss,
+    /// Indicates that a memoized parser returned a cached result without running,
+    /// containing the close event of the cached result.
+    MemoHit(Box<TraceEventType>),
+    /// Indicates that a parser silenced with [silence_tree!](crate::silence_tree!) returned,
+    /// containing its close event and the number of nested invocations hidden.
+    Silenced {
+        /// The close event of the silenced parser.
+        result: Box<TraceEventType>,
+        /// The number of parsers invoked inside the silenced parser, memoization hits included.
+        hidden: usize,
+    },
+    /// Summarizes the parsers nested below the recording depth of the trace, which weren't
+    /// recorded individually. See [Trace::record_depth](crate::traces::Trace::record_depth).
+    Summary(TraceSummary),
+}
+
+impl TraceEventType {
+    /// Returns `true` if the event closes a parser.
+    pub fn is_close(&self) -> bool {
+        matches!(
+            self,
+            TraceEventType::CloseOk(_)
+                | TraceEventType::CloseError(_)
+                | TraceEventType::CloseFailure(_)
+                | TraceEventType::CloseIncomplete(_)
+        )
+    }
+}
+
+/// The activity of the parsers nested below the recording depth of a trace.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TraceSummary {
+    /// The number of parsers invoked, memoization hits included.
+    pub invocations: usize,
+    /// The deepest nesting level reached.
+    pub max_level: usize,
+    /// The number of parsers that returned `Ok`.
+    pub ok: usize,
+    /// The number of parsers that returned an `Error`.
+    pub error: usize,
+    /// The number of parsers that returned a `Failure`.
+    pub failure: usize,
+    /// The number of parsers that returned `Incomplete`.
+    pub incomplete: usize,
+}
+
+impl TraceSummary {
+    /// Counts the invocation of a parser at the given level.
+    pub(crate) fn open(&mut self, level: usize) {
+        self.invocations += 1;
+        self.max_level = self.max_level.max(level);
+    }
+
+    /// Counts the result of a parser.
+    pub(crate) fn close<I, O, E>(&mut self, result: &nom::IResult<I, O, E>) {
+        match result {
+            Ok(_) => self.ok += 1,
+            Err(nom::Err::Error(_)) => self.error += 1,
+            Err(nom::Err::Failure(_)) => self.failure += 1,
+            Err(nom::Err::Incomplete(_)) => self.incomplete += 1,
+        }
+    }
+
+    /// Counts the result of a parser, from its close event. Other events are ignored.
+    #[cfg(feature = "trace-silencing")]
+    pub(crate) fn record(&mut self, result: &TraceEventType) {
+        match result {
+            TraceEventType::CloseOk(_) => self.ok += 1,
+            TraceEventType::CloseError(_) => self.error += 1,
+            TraceEventType::CloseFailure(_) => self.failure += 1,
+            TraceEventType::CloseIncomplete(_) => self.incomplete += 1,
+            _ => {}
+        }
+    }
+}
+
+impl Display for TraceSummary {
+    /// Formats the summary on a single line, like
+    /// `… 12 nested invocations, max level 7: 9 ok, 3 error`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "… {} nested invocations, max level {}",
+            self.invocations, self.max_level
+        )?;
+
+        let results = [
+            (self.ok, "ok"),
+            (self.error, "error"),
+            (self.failure, "failure"),
+            (self.incomplete, "incomplete"),
+        ];
+        let mut separator = ":";
+        for (count, result) in results.iter().filter(|(count, _)| *count > 0) {
+            write!(f, "{} {} {}", separator, count, result)?;
+            separator = ",";
+        }
+        Ok(())
+    }
 }
 
 /// Represents a single trace event in the parsing process.
@@ -112,6 +214,34 @@ impl Display for TraceEvent {
                     i,
                     ansi::TEXT_INVERSE_RESET
                 ),
+                TraceEventType::MemoMiss => format!(
+                    "{}{}{}(\"{}\") {}memo miss",
+                    ansi::TEXT_UNDERLINE,
+                    self.location,
+                    ansi::TEXT_UNDERLINE_RESET,
+                    input,
+                    ansi::FG_CYAN,
+                ),
+                TraceEventType::MemoHit(result) => format!(
+                    "{}{}{}(\"{}\") {}memo hit {}",
+                    ansi::TEXT_UNDERLINE,
+                    self.location,
+                    ansi::TEXT_UNDERLINE_RESET,
+                    input,
+                    ansi::FG_CYAN,
+                    close_content(result),
+                ),
+                TraceEventType::Silenced { result, hidden } => format!(
+                    "{}{}{}(\"{}\") {}silenced, {} nested invocations hidden {}",
+                    ansi::TEXT_UNDERLINE,
+                    self.location,
+                    ansi::TEXT_UNDERLINE_RESET,
+                    input,
+                    ansi::FG_CYAN,
+                    hidden,
+                    close_content(result),
+                ),
+                TraceEventType::Summary(summary) => format!("{}{}", ansi::FG_CYAN, summary),
             };
 
             writeln!(
@@ -133,6 +263,21 @@ impl Display for TraceEvent {
                 TraceEventType::CloseError(e) => format!("-> Error({})", e),
                 TraceEventType::CloseFailure(e) => format!("-> Failure({})", e),
                 TraceEventType::CloseIncomplete(i) => format!("-> Incomplete({:?})", i),
+                TraceEventType::MemoMiss => format!("{}(\"{}\") memo miss", self.location, input),
+                TraceEventType::MemoHit(result) => format!(
+                    "{}(\"{}\") memo hit {}",
+                    self.location,
+                    input,
+                    close_content(result)
+                ),
+                TraceEventType::Silenced { result, hidden } => format!(
+                    "{}(\"{}\") silenced, {} nested invocations hidden {}",
+                    self.location,
+                    input,
+                    hidden,
+                    close_content(result)
+                ),
+                TraceEventType::Summary(summary) => summary.to_string(),
             };
 
             writeln!(f, "{}{}{}", indent, content, ctx)
@@ -140,10 +285,55 @@ impl Display for TraceEvent {
     }
 }
 
+impl TraceEvent {
+    /// Formats the event on a single line, without colors or indentation.
+    pub(crate) fn plain(&self) -> String {
+        let content = match &self.event {
+            TraceEventType::Open => format!("{}(\"{}\")", self.location, self.input),
+            TraceEventType::MemoMiss => format!("{}(\"{}\") memo miss", self.location, self.input),
+            TraceEventType::MemoHit(result) => format!(
+                "{}(\"{}\") memo hit {}",
+                self.location,
+                self.input,
+                close_content(result)
+            ),
+            TraceEventType::Silenced { result, hidden } => format!(
+                "{}(\"{}\") silenced, {} nested invocations hidden {}",
+                self.location,
+                self.input,
+                hidden,
+                close_content(result)
+            ),
+            TraceEventType::Summary(summary) => summary.to_string(),
+            event => close_content(event),
+        };
+
+        match self.context {
+            Some(context) => format!("{}[{}]", content, context),
+            None => content,
+        }
+    }
+}
+
+/// Formats the result part of a close event, without colors or indentation.
+fn close_content(event: &TraceEventType) -> String {
+    match event {
+        TraceEventType::CloseOk(result) => format!("-> Ok({})", result),
+        TraceEventType::CloseError(e) => format!("-> Error({})", e),
+        TraceEventType::CloseFailure(e) => format!("-> Failure({})", e),
+        TraceEventType::CloseIncomplete(i) => format!("-> Incomplete({:?})", i),
+        TraceEventType::Open
+        | TraceEventType::MemoMiss
+        | TraceEventType::MemoHit(_)
+        | TraceEventType::Silenced { .. }
+        | TraceEventType::Summary(_) => String::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
-        crate::events::{TraceEvent, TraceEventType},
+        crate::events::{TraceEvent, TraceEventType, TraceSummary},
         std::num::NonZero,
     };
 
@@ -216,4 +406,65 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_display_memo_hit() {
+        let display = TraceEvent {
+            level: 1,
+            location: "test_location",
+            context: None,
+            input: "test_input".to_string(),
+            event: TraceEventType::MemoHit(Box::new(TraceEventType::CloseOk("ok".to_string()))),
+        }
+        .to_string();
+
+        assert!(display.contains("test_location"));
+        assert!(display.contains("memo hit"));
+        assert!(display.contains("-> Ok(ok)"));
+    }
+
+    #[test]
+    fn test_display_summary() {
+        let summary = TraceSummary {
+            invocations: 12,
+            max_level: 7,
+            ok: 9,
+            error: 3,
+            ..TraceSummary::default()
+        };
+        assert_eq!(
+            summary.to_string(),
+            "… 12 nested invocations, max level 7: 9 ok, 3 error"
+        );
+
+        let display = TraceEvent {
+            level: 3,
+            location: "test_location",
+            context: None,
+            input: "test_input".to_string(),
+            event: TraceEventType::Summary(summary),
+        }
+        .to_string();
+        assert!(display.starts_with("| | | "));
+        assert!(display.contains("12 nested invocations"));
+    }
+
+    #[test]
+    fn test_display_silenced() {
+        let display = TraceEvent {
+            level: 1,
+            location: "test_location",
+            context: None,
+            input: "test_input".to_string(),
+            event: TraceEventType::Silenced {
+                result: Box::new(TraceEventType::CloseError("error".to_string())),
+                hidden: 3,
+            },
+        }
+        .to_string();
+
+        assert!(display.contains("test_location"));
+        assert!(display.contains("silenced, 3 nested invocations hidden"));
+        assert!(display.contains("-> Error(error)"));
+    }
 }
@@ -0,0 +1,166 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+use {
+    crate::{frames::Frame, traces::Trace},
+    std::fmt::{Display, Formatter},
+};
+
+/// Describes the furthest position a failed parse reached, and what was expected there.
+///
+/// This is synthesized from a [Trace] after a failed parse: among the leaf parsers that
+/// returned an error, the ones that failed furthest into the input tell what the input
+/// should have contained at that position.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FurthestFailure {
+    /// The byte offset of the failure in the source.
+    pub offset: usize,
+    /// The line of the failure, starting at 1.
+    pub line: usize,
+    /// The column of the failure in characters, starting at 1.
+    pub column: usize,
+    /// The names (context, or location if no context is set) of the leaf parsers that
+    /// failed at this position, without duplicates.
+    pub expected: Vec<&'static str>,
+}
+
+impl FurthestFailure {
+    /// Finds the furthest failure in a trace.
+    ///
+    /// The trace is expected to hold a single parse of `source`: the events of other parses,
+    /// like earlier parses of other inputs, would give meaningless positions. Reset the trace
+    /// before the parse, for example with [reset_trace!](crate::reset_trace!).
+    ///
+    /// # Arguments
+    ///
+    /// * `trace` - The trace of the failed parse.
+    /// * `source` - The complete input given to the parser, used to compute the position.
+    ///
+    /// # Returns
+    ///
+    /// The furthest failure, or `None` if no leaf parser failed in the trace.
+    pub fn from_trace(trace: &Trace, source: &str) -> Option<Self> {
+        let frames = Frame::from_events(&trace.events);
+        let failures: Vec<_> = frames
+            .iter()
+            .flat_map(Frame::iter)
+            .filter(|frame| frame.is_leaf() && frame.is_failure())
+            .collect();
+
+        let remaining = failures.iter().map(|frame| frame.open.input.len()).min()?;
+
+        let mut expected = Vec::new();
+        for frame in failures {
+            if frame.open.input.len() == remaining && !expected.contains(&frame.name()) {
+                expected.push(frame.name());
+            }
+        }
+
+        let offset = source.len().saturating_sub(remaining);
+        let consumed = source.get(..offset).unwrap_or(source);
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed
+            .rsplit('\n')
+            .next()
+            .map(|l| l.chars().count())
+            .unwrap_or(0)
+            + 1;
+
+        Some(Self {
+            offset,
+            line,
+            column,
+            expected,
+        })
+    }
+}
+
+impl Display for FurthestFailure {
+    /// Formats the failure as a human-readable message.
+    ///
+    /// For example: ``at line 3, col 14: expected one of `number`, `string`, `[` ``
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at line {}, col {}: expected ", self.line, self.column)?;
+        if self.expected.len() > 1 {
+            write!(f, "one of ")?;
+        }
+
+        for (i, name) in self.expected.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "`{}`", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{reset_trace, tr, DEFAULT_TAG, TRACE_TAGS},
+        nom::{
+            branch::alt,
+            bytes::complete::tag,
+            character::complete::{digit1, multispace0},
+            error::VerboseError,
+            sequence::{preceded, tuple},
+            IResult,
+        },
+    };
+
+    fn value(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+        tr(
+            DEFAULT_TAG,
+            None,
+            "value",
+            preceded(
+                multispace0,
+                alt((
+                    tr(DEFAULT_TAG, Some("number"), "number", digit1),
+                    tr(DEFAULT_TAG, Some("string"), "string", tag("\"\"")),
+                    tr(DEFAULT_TAG, None, "[", tag("[")),
+                )),
+            ),
+        )(input)
+    }
+
+    #[test]
+    fn test_furthest_failure() {
+        reset_trace!();
+
+        let source = "1,\n2,\n  x";
+        let result = tuple((value, tag(","), value, tag(","), value))(source);
+        assert!(result.is_err());
+
+        let failure = TRACE_TAGS
+            .with(|tags| FurthestFailure::from_trace(&tags.borrow().traces[DEFAULT_TAG], source))
+            .unwrap();
+
+        assert_eq!(failure.offset, 8);
+        assert_eq!(failure.line, 3);
+        assert_eq!(failure.column, 3);
+        assert_eq!(failure.expected, vec!["number", "string", "["]);
+        assert_eq!(
+            failure.to_string(),
+            "at line 3, col 3: expected one of `number`, `string`, `[`"
+        );
+    }
+
+    #[test]
+    fn test_furthest_failure_single() {
+        let failure = FurthestFailure {
+            offset: 0,
+            line: 1,
+            column: 1,
+            expected: vec!["number"],
+        };
+        assert_eq!(failure.to_string(), "at line 1, col 1: expected `number`");
+    }
+
+    #[test]
+    fn test_furthest_failure_no_failure() {
+        assert!(FurthestFailure::from_trace(&Trace::default(), "").is_none());
+    }
+}
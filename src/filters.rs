@@ -0,0 +1,298 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Record-time filters.
+//!
+//! Filters decide, when a parser is traced, whether its events are recorded and printed. They
+//! hide noise parsers, like whitespace or comments, without silencing them one by one in the
+//! grammar.
+//!
+//! An excluded parser is hidden with all the parsers nested in it. When a tag has include
+//! filters, only the included parsers and the parsers nested in them are recorded.
+//!
+//! ```
+//! use nom_tracer::{filters::{TraceFilter, TraceFilters}, tags::TraceTags, DEFAULT_TAG};
+//!
+//! let mut tags = TraceTags::new();
+//! tags.set_filters(
+//!     DEFAULT_TAG,
+//!     TraceFilters::new()
+//!         .exclude(TraceFilter::name("ws"))
+//!         .exclude(TraceFilter::glob("*comment*")),
+//! );
+//! ```
+
+use crate::snapshot::normalize_location;
+
+/// A filter matching parsers by their location or context.
+#[derive(Clone, Debug)]
+pub enum TraceFilter {
+    /// Matches the parsers whose context, or function name, is equal to the given name.
+    Name(String),
+    /// Matches the parsers whose context or location matches the given glob pattern, where
+    /// `*` matches any sequence of characters, and `?` any single character.
+    Glob(String),
+    /// Matches the parsers whose context or location matches the given regular expression.
+    ///
+    /// This variant is only available when the `trace-regex` feature is enabled.
+    #[cfg(feature = "trace-regex")]
+    Regex(regex::Regex),
+}
+
+impl TraceFilter {
+    /// Creates a filter matching a context, or a function name, exactly.
+    pub fn name(name: impl Into<String>) -> Self {
+        TraceFilter::Name(name.into())
+    }
+
+    /// Creates a filter matching a context or a location with a glob pattern.
+    pub fn glob(pattern: impl Into<String>) -> Self {
+        TraceFilter::Glob(pattern.into())
+    }
+
+    /// Creates a filter matching a context or a location with a regular expression.
+    ///
+    /// This method is only available when the `trace-regex` feature is enabled.
+    #[cfg(feature = "trace-regex")]
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        regex::Regex::new(pattern).map(TraceFilter::Regex)
+    }
+
+    /// Returns `true` if the filter matches the parser with the given location and context.
+    pub fn matches(&self, location: &str, context: Option<&str>) -> bool {
+        let matches = |f: &dyn Fn(&str) -> bool| f(location) || context.is_some_and(f);
+
+        match self {
+            TraceFilter::Name(name) => {
+                normalize_location(location) == name || context == Some(name.as_str())
+            }
+            TraceFilter::Glob(pattern) => matches(&|s| glob_matches(pattern, s)),
+            #[cfg(feature = "trace-regex")]
+            TraceFilter::Regex(regex) => matches(&|s| regex.is_match(s)),
+        }
+    }
+}
+
+impl PartialEq for TraceFilter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TraceFilter::Name(a), TraceFilter::Name(b)) => a == b,
+            (TraceFilter::Glob(a), TraceFilter::Glob(b)) => a == b,
+            #[cfg(feature = "trace-regex")]
+            (TraceFilter::Regex(a), TraceFilter::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+/// The include and exclude filters of a trace.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceFilters {
+    /// If not empty, only the parsers matching one of these filters, and the parsers nested in
+    /// them, are recorded.
+    pub include: Vec<TraceFilter>,
+    /// The parsers matching one of these filters, and the parsers nested in them, aren't
+    /// recorded.
+    pub exclude: Vec<TraceFilter>,
+}
+
+impl TraceFilters {
+    /// Creates an empty set of filters, recording every parser.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an include filter.
+    pub fn include(mut self, filter: TraceFilter) -> Self {
+        self.include.push(filter);
+        self
+    }
+
+    /// Adds an exclude filter.
+    pub fn exclude(mut self, filter: TraceFilter) -> Self {
+        self.exclude.push(filter);
+        self
+    }
+
+    /// Returns `true` if there are no filters.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub(crate) fn includes(&self, location: &str, context: Option<&str>) -> bool {
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|filter| filter.matches(location, context))
+    }
+
+    pub(crate) fn excludes(&self, location: &str, context: Option<&str>) -> bool {
+        self.exclude
+            .iter()
+            .any(|filter| filter.matches(location, context))
+    }
+}
+
+/// The state of the filters while a trace is recorded.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FilterState {
+    /// The level of the excluded parser being run, if any.
+    hidden: Option<usize>,
+    /// The level of the included parser being run, if any.
+    included: Option<usize>,
+}
+
+impl FilterState {
+    /// Returns `true` if the opening of a parser at the given level is recorded.
+    pub(crate) fn open(
+        &mut self,
+        filters: &TraceFilters,
+        level: usize,
+        location: &str,
+        context: Option<&str>,
+    ) -> bool {
+        if self.hidden.is_some() {
+            return false;
+        }
+        if filters.excludes(location, context) {
+            self.hidden = Some(level);
+            return false;
+        }
+        if filters.include.is_empty() || self.included.is_some() {
+            return true;
+        }
+        if filters.includes(location, context) {
+            self.included = Some(level);
+            return true;
+        }
+        false
+    }
+
+    /// Returns `true` if the closing of a parser at the given level is recorded.
+    pub(crate) fn close(&mut self, filters: &TraceFilters, level: usize) -> bool {
+        if let Some(hidden) = self.hidden {
+            if hidden == level {
+                self.hidden = None;
+            }
+            return false;
+        }
+
+        match self.included {
+            Some(included) => {
+                if included == level {
+                    self.included = None;
+                }
+                true
+            }
+            None => filters.include.is_empty(),
+        }
+    }
+
+    /// Returns `true` if an event at the given level that doesn't open or close a parser,
+    /// like a memoization lookup or a silenced parser, is recorded.
+    #[cfg(any(feature = "trace-memo", feature = "trace-silencing"))]
+    pub(crate) fn event(
+        &self,
+        filters: &TraceFilters,
+        location: &str,
+        context: Option<&str>,
+    ) -> bool {
+        self.hidden.is_none()
+            && !filters.excludes(location, context)
+            && (self.included.is_some() || filters.includes(location, context))
+    }
+}
+
+/// Returns `true` if the string matches the glob pattern.
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    i = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("ws", "ws"));
+        assert!(!glob_matches("ws", "ws2"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*comment*", "line_comment::{{closure}}"));
+        assert!(glob_matches("json_?", "json_a"));
+        assert!(!glob_matches("json_?", "json_ab"));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        assert!(TraceFilter::name("ws").matches("ws", None));
+        assert!(TraceFilter::name("ws").matches("value::{{closure}}", Some("ws")));
+        assert!(TraceFilter::name("ws").matches("ws::{{closure}}", None));
+        assert!(!TraceFilter::name("ws").matches("value", Some("wsx")));
+        assert!(TraceFilter::glob("*space*").matches("multispace0", None));
+    }
+
+    #[test]
+    fn test_filter_state() {
+        let filters = TraceFilters::new().exclude(TraceFilter::name("ws"));
+        let mut state = FilterState::default();
+
+        assert!(state.open(&filters, 0, "value", None));
+        assert!(!state.open(&filters, 1, "ws", None));
+        assert!(!state.open(&filters, 2, "inner", None));
+        #[cfg(any(feature = "trace-memo", feature = "trace-silencing"))]
+        assert!(!state.event(&filters, "memo", None));
+        assert!(!state.close(&filters, 2));
+        assert!(!state.close(&filters, 1));
+        assert!(state.close(&filters, 0));
+
+        let filters = TraceFilters::new().include(TraceFilter::name("string"));
+        let mut state = FilterState::default();
+
+        assert!(!state.open(&filters, 0, "value", None));
+        assert!(state.open(&filters, 1, "string", None));
+        assert!(state.open(&filters, 2, "char", None));
+        assert!(state.close(&filters, 2));
+        assert!(state.close(&filters, 1));
+        assert!(!state.close(&filters, 0));
+    }
+
+    #[cfg(feature = "trace-regex")]
+    #[test]
+    fn test_regex_filter() {
+        let filter = TraceFilter::regex("^(ws|comment)$").unwrap();
+        assert!(filter.matches("ws", None));
+        assert!(filter.matches("value", Some("comment")));
+        assert!(!filter.matches("value", None));
+        assert_eq!(filter, TraceFilter::regex("^(ws|comment)$").unwrap());
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::events::{TraceEvent, TraceEventType};
+
+/// Represents a single parser invocation, rebuilt from the events of a trace.
+///
+/// A frame pairs an [Open](TraceEventType::Open) event with its matching close event and
+/// holds the invocations nested inside it. Events that don't open a parser, like memoization
+/// lookups or summaries, become frames without a close event or children.
+#[derive(Clone)]
+pub struct Frame<'a> {
+    /// The event that started this frame.
+    pub open: &'a TraceEvent,
+    /// The event that ended this frame, if the parser returned.
+    pub close: Option<&'a TraceEvent>,
+    /// The frames nested in this frame, in invocation order.
+    pub children: Vec<Frame<'a>>,
+}
+
+impl<'a> Frame<'a> {
+    /// Rebuilds the tree of frames from a sequence of events.
+    ///
+    /// Close events without a matching open event are ignored, and frames that are still
+    /// open at the end of the sequence are returned without a close event.
+    pub fn from_events(events: impl IntoIterator<Item = &'a TraceEvent>) -> Vec<Frame<'a>> {
+        let mut roots = Vec::new();
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+
+        for event in events {
+            match event.event {
+                TraceEventType::Open => stack.push(Frame::new(event)),
+                TraceEventType::CloseOk(_)
+                | TraceEventType::CloseError(_)
+                | TraceEventType::CloseFailure(_)
+                | TraceEventType::CloseIncomplete(_) => {
+                    if let Some(mut frame) = stack.pop() {
+                        frame.close = Some(event);
+                        push_frame(&mut roots, &mut stack, frame);
+                    }
+                }
+                TraceEventType::MemoMiss
+                | TraceEventType::MemoHit(_)
+                | TraceEventType::Silenced { .. }
+                | TraceEventType::Summary(_) => {
+                    push_frame(&mut roots, &mut stack, Frame::new(event));
+                }
+            }
+        }
+
+        while let Some(frame) = stack.pop() {
+            push_frame(&mut roots, &mut stack, frame);
+        }
+
+        roots
+    }
+
+    fn new(open: &'a TraceEvent) -> Self {
+        Self {
+            open,
+            close: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the result of this frame, if any.
+    ///
+    /// For a memoization hit, this is the cached result.
+    pub fn result(&self) -> Option<&'a TraceEventType> {
+        match (&self.open.event, self.close) {
+            (TraceEventType::MemoHit(result), _) | (TraceEventType::Silenced { result, .. }, _) => {
+                Some(result)
+            }
+            (_, Some(close)) => Some(&close.event),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of this frame: its context if set, or its location otherwise.
+    pub fn name(&self) -> &'static str {
+        self.open.context.unwrap_or(self.open.location)
+    }
+
+    /// Returns `true` if no parser was invoked inside this frame.
+    pub fn is_leaf(&self) -> bool {
+        self.children
+            .iter()
+            .all(|child| matches!(child.open.event, TraceEventType::MemoMiss))
+    }
+
+    /// Returns `true` if this frame returned an error, a failure, or an incomplete result.
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self.result(),
+            Some(
+                TraceEventType::CloseError(_)
+                    | TraceEventType::CloseFailure(_)
+                    | TraceEventType::CloseIncomplete(_)
+            )
+        )
+    }
+
+    /// Iterates over this frame and all its nested frames, depth-first.
+    pub fn iter(&self) -> impl Iterator<Item = &Frame<'a>> {
+        let mut stack = vec![self];
+
+        std::iter::from_fn(move || {
+            let frame = stack.pop()?;
+            stack.extend(frame.children.iter().rev());
+            Some(frame)
+        })
+    }
+}
+
+fn push_frame<'a>(roots: &mut Vec<Frame<'a>>, stack: &mut [Frame<'a>], frame: Frame<'a>) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(frame),
+        None => roots.push(frame),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::traces::Trace, nom::error::VerboseError};
+
+    #[test]
+    fn test_frames_from_events() {
+        let mut trace = Trace::default();
+        trace.open(None, "ab", "outer", false);
+        trace.open(None, "ab", "inner_a", false);
+        trace.close::<_, _, VerboseError<&str>>(None, "ab", "inner_a", &Ok(("b", "a")), false);
+        trace.open(Some("b"), "b", "inner_b", false);
+        trace.close::<_, &str, _>(
+            None,
+            "b",
+            "inner_b",
+            &Err(nom::Err::Error(VerboseError::<&str> { errors: vec![] })),
+            false,
+        );
+        trace.close::<_, &str, _>(
+            None,
+            "ab",
+            "outer",
+            &Err(nom::Err::Error(VerboseError::<&str> { errors: vec![] })),
+            false,
+        );
+        trace.open(None, "c", "unclosed", false);
+
+        let frames = Frame::from_events(&trace.events);
+        assert_eq!(frames.len(), 2);
+
+        let outer = &frames[0];
+        assert_eq!(outer.name(), "outer");
+        assert!(!outer.is_leaf());
+        assert!(outer.is_failure());
+        assert_eq!(outer.children.len(), 2);
+        assert!(outer.children[0].is_leaf());
+        assert!(!outer.children[0].is_failure());
+        assert_eq!(outer.children[1].name(), "b");
+        assert!(outer.children[1].is_failure());
+        assert_eq!(outer.iter().count(), 3);
+
+        assert!(frames[1].close.is_none());
+        assert!(frames[1].result().is_none());
+    }
+}
@@ -3,24 +3,65 @@
 
 #[cfg(feature = "trace")]
 use crate::tags::TraceTags;
-#[cfg(feature = "trace-silencing")]
+#[cfg(feature = "trace")]
 use crate::traces::Trace;
 #[cfg(feature = "trace-context")]
 use nom::error::ContextError;
+#[cfg(feature = "trace-memo")]
+use {
+    crate::memo::MemoTable,
+    std::{cell::RefCell, rc::Rc},
+};
 use {
     nom::{IResult, Parser},
-    std::fmt::Debug,
+    std::{borrow::Cow, fmt::Debug},
 };
 
 #[cfg(feature = "trace-color")]
 #[allow(dead_code)]
 pub(crate) mod ansi;
 #[cfg(feature = "trace")]
+pub mod assertions;
+#[cfg(feature = "trace")]
+pub mod collector;
+#[cfg(feature = "trace")]
+pub mod config;
+#[cfg(feature = "trace")]
+pub mod context;
+#[cfg(feature = "trace-coverage")]
+pub mod coverage;
+#[cfg(feature = "trace-miette")]
+pub mod diagnostic;
+#[cfg(feature = "trace")]
+pub mod diff;
+#[cfg(feature = "trace-env")]
+pub mod env;
+#[cfg(feature = "trace")]
 pub mod events;
 #[cfg(feature = "trace")]
+pub mod failure;
+#[cfg(feature = "trace")]
+pub mod filters;
+#[cfg(feature = "trace")]
+pub mod frames;
+#[cfg(feature = "trace-memo")]
+pub mod memo;
+#[cfg(feature = "trace-registry")]
+pub mod registry;
+#[cfg(feature = "trace")]
+pub mod session;
+#[cfg(feature = "trace")]
+pub mod snapshot;
+#[cfg(feature = "trace")]
+pub mod stats;
+#[cfg(feature = "trace")]
 pub mod tags;
 #[cfg(feature = "trace")]
+pub mod tracer;
+#[cfg(feature = "trace")]
 pub mod traces;
+#[cfg(feature = "trace")]
+pub mod view;
 
 pub mod macros;
 
@@ -40,7 +81,7 @@ thread_local! {
     /// Usage of [TRACE_TAGS] is typically wrapped in the [tr] and [tr_tag_ctx] functions,
     /// which provide a more convenient interface for adding trace events.
     #[cfg(feature = "trace")]
-    pub static TRACE_TAGS: std::cell::RefCell<TraceTags> = std::cell::RefCell::new(TraceTags::new());
+    pub static TRACE_TAGS: std::cell::RefCell<TraceTags> = std::cell::RefCell::new(initial_trace_tags());
 
     /// Thread-local storage for silent tracing (used with trace-silencing feature)
     #[cfg(feature = "trace-silencing")]
@@ -51,6 +92,19 @@ thread_local! {
     pub static TREE_SILENCE_LEVELS: std::cell::RefCell<Vec<usize>> = const { std::cell::RefCell::new(vec![]) };
 }
 
+/// Creates the thread-local [TraceTags], configured from the environment when the `trace-env`
+/// feature is enabled.
+#[cfg(feature = "trace")]
+fn initial_trace_tags() -> TraceTags {
+    #[cfg(feature = "trace-env")]
+    let mut tags = TraceTags::from_env();
+    #[cfg(not(feature = "trace-env"))]
+    let mut tags = TraceTags::new();
+
+    tags.collect = true;
+    tags
+}
+
 #[cfg(feature = "trace-context")]
 pub trait TraceError<I>: Debug + ContextError<I> {}
 #[cfg(feature = "trace-context")]
@@ -68,13 +122,13 @@ impl<I, E> TraceError<I> for E where E: Debug {}
 ///
 /// # Arguments
 ///
-/// * `tag` - A static string used to categorize the trace events.
+/// * `tag` - A string used to categorize the trace events, usually static.
 /// * `context` - An optional static string providing additional context for the trace.
 /// * `name` - A static string identifying the parser being traced.
 /// * `parser` - The parser function to be wrapped with tracing.
 pub fn tr<I, O, E, F>(
-    #[cfg(feature = "trace")] tag: &'static str,
-    #[cfg(not(feature = "trace"))] _tag: &'static str,
+    #[cfg(feature = "trace")] tag: impl Into<Cow<'static, str>>,
+    #[cfg(not(feature = "trace"))] _tag: impl Into<Cow<'static, str>>,
     #[cfg(any(feature = "trace", feature = "trace-context"))] context: Option<&'static str>,
     #[cfg(not(any(feature = "trace", feature = "trace-context")))] _context: Option<&'static str>,
     #[cfg(feature = "trace")] name: &'static str,
@@ -90,47 +144,22 @@ where
 {
     #[cfg(feature = "trace")]
     {
+        let tag = tag.into();
+
         move |input: I| {
             let input1 = input.clone();
             let input2 = input.clone();
             #[cfg(feature = "trace-context")]
             let input3 = input.clone();
 
-            #[cfg(feature = "trace-silencing")]
-            let silent = TREE_SILENCE_LEVELS.with(|levels| !levels.borrow().is_empty());
-
-            #[cfg(feature = "trace-silencing")]
-            if silent {
-                TRACE_SILENT.with(|trace| {
-                    (*trace.borrow_mut()).open(context, input1, name, true);
-                });
-            } else {
-                TRACE_TAGS.with(|tags| {
-                    (*tags.borrow_mut()).open(tag, context, input1, name, false);
-                });
-            };
-            #[cfg(not(feature = "trace-silencing"))]
-            TRACE_TAGS.with(|tags| {
-                (*tags.borrow_mut()).open(tag, context, input1, name, false);
-            });
+            context::with_recorder(|recorder| recorder.open(&tag, context, input1, name));
 
             let res = parser.parse(input);
 
-            #[cfg(feature = "trace-silencing")]
-            if silent {
-                TRACE_SILENT.with(|trace| {
-                    (*trace.borrow_mut()).close(context, input2, name, &res, true);
-                });
-            } else {
-                TRACE_TAGS.with(|tags| {
-                    (*tags.borrow_mut()).close(tag, context, input2, name, &res, false);
-                });
-            }
+            #[cfg(feature = "trace-coverage")]
+            coverage::record_global(name, context, &res);
 
-            #[cfg(not(feature = "trace-silencing"))]
-            TRACE_TAGS.with(|tags| {
-                (*tags.borrow_mut()).close(tag, context, input2, name, &res, false);
-            });
+            context::with_recorder(|recorder| recorder.close(&tag, context, input2, name, &res));
 
             #[cfg(not(feature = "trace-context"))]
             return res;
@@ -165,17 +194,19 @@ where
 /// Function to silence tracing for a subtree of parsers.
 ///
 /// This is used to reduce noise in the trace output for well-tested or less interesting
-/// parts of the parser.
+/// parts of the parser. The parsers nested in the silenced parser are recorded in the silent
+/// trace, and the tagged trace gets a single summary line for the silenced parser, unless its
+/// [TraceSilence](config::TraceSilence) mode is [Hide](config::TraceSilence::Hide).
 ///
 /// # Arguments
 ///
-/// * `tag` - A static string used to categorize the trace events.
+/// * `tag` - A string used to categorize the trace events, usually static.
 /// * `context` - An optional static string providing additional context for the trace.
 /// * `name` - A static string identifying the parser being silenced.
 /// * `parser` - The parser function to be silenced.
 #[cfg(feature = "trace-silencing")]
 pub fn silence_tree<I, O, E, F>(
-    tag: &'static str,
+    tag: impl Into<Cow<'static, str>>,
     context: Option<&'static str>,
     name: &'static str,
     mut parser: F,
@@ -187,32 +218,22 @@ where
     O: Debug,
     E: TraceError<I>,
 {
+    let tag = tag.into();
+
     move |input: I| {
         let input1 = input.clone();
         let input2 = input.clone();
         let input3 = input.clone();
 
-        let silent_cut_level = TRACE_SILENT.with(|tags| tags.borrow_mut().level);
-        let cut_level = TRACE_TAGS.with(|tags| (*tags.borrow_mut()).level_for_tag(tag));
-        let cut_level = silent_cut_level.max(cut_level);
-
-        TREE_SILENCE_LEVELS.with(|levels| {
-            (*levels.borrow_mut()).push(cut_level);
-        });
-
-        TRACE_SILENT.with(|trace| {
-            (*trace.borrow_mut()).set_level(cut_level);
-            (*trace.borrow_mut()).open(context, input1, name, true);
-        });
+        context::with_recorder(|recorder| recorder.open_silence(&tag, context, input1, name));
 
         let res = parser.parse(input);
 
-        TRACE_SILENT.with(|trace| {
-            (*trace.borrow_mut()).close(context, input2, name, &res, true);
-        });
+        #[cfg(feature = "trace-coverage")]
+        coverage::record_global(name, context, &res);
 
-        TREE_SILENCE_LEVELS.with(|levels| {
-            (*levels.borrow_mut()).pop();
+        context::with_recorder(|recorder| {
+            recorder.close_silence(&tag, context, input2, name, &res)
         });
 
         #[cfg(feature = "trace-context")]
@@ -223,6 +244,71 @@ where
     }
 }
 
+/// Function to memoize the results of a parser.
+///
+/// Results are cached by input position in a [MemoTable] owned by the returned parser, so when
+/// it is invoked again at the same position (typically by backtracking `alt` branches), it
+/// returns the cached result instead of re-parsing. Clones of the returned parser share its
+/// table, so it can be used in several branches. Cache lookups are recorded as
+/// [MemoMiss](events::TraceEventType::MemoMiss) and [MemoHit](events::TraceEventType::MemoHit)
+/// events.
+///
+/// Positions are offsets from the start input of the parse, and the cache starts over when the
+/// parser is invoked on another input buffer.
+///
+/// # Arguments
+///
+/// * `tag` - A string used to categorize the trace events, usually static.
+/// * `context` - An optional static string providing additional context for the trace.
+/// * `name` - A static string identifying the parser being memoized.
+/// * `parser` - The parser function to be memoized.
+///
+/// # Panics
+///
+/// Panics if the parser invokes itself through a clone of the returned parser.
+#[cfg(feature = "trace-memo")]
+pub fn memoize<I, O, E, F>(
+    tag: impl Into<Cow<'static, str>>,
+    context: Option<&'static str>,
+    name: &'static str,
+    parser: F,
+) -> impl FnMut(I) -> IResult<I, O, E> + Clone
+where
+    I: AsRef<str>,
+    F: Parser<I, O, E>,
+    I: Clone,
+    O: Clone + Debug,
+    E: Clone + TraceError<I>,
+{
+    let tag = tag.into();
+    let parser = Rc::new(RefCell::new(parser));
+    let table = Rc::new(RefCell::new(MemoTable::default()));
+
+    move |input: I| {
+        let offset = table.borrow_mut().offset(&input);
+        let cached = table.borrow().get(offset);
+
+        context::with_recorder(|recorder| {
+            recorder.memo(&tag, context, input.clone(), name, cached.as_ref())
+        });
+
+        if let Some(res) = cached {
+            #[cfg(feature = "trace-coverage")]
+            coverage::record_global(name, context, &res);
+
+            return res;
+        }
+
+        let res = parser
+            .try_borrow_mut()
+            .expect("a memoized parser can't invoke itself")
+            .parse(input);
+        table.borrow_mut().insert(offset, res.clone());
+
+        res
+    }
+}
+
 /// Helper function to add context to error results.
 ///
 /// This is used when the trace-context feature is enabled to provide more
@@ -251,14 +337,14 @@ where
 ///
 /// # Arguments
 ///
-/// * `tag` - A static string identifying the tag for which to retrieve the trace.
+/// * `tag` - A string identifying the tag for which to retrieve the trace.
 ///
 /// # Returns
 ///
 /// Returns a string representation of the trace, or a message if no trace is found.
 pub fn get_trace_for_tag(
-    #[cfg(feature = "trace")] tag: &'static str,
-    #[cfg(not(feature = "trace"))] _tag: &'static str,
+    #[cfg(feature = "trace")] tag: &str,
+    #[cfg(not(feature = "trace"))] _tag: &str,
 ) -> Option<String> {
     #[cfg(feature = "trace")]
     {
@@ -269,15 +355,95 @@ pub fn get_trace_for_tag(
     None
 }
 
+/// Retrieves the trace for a specific tag, rendered for snapshot tests.
+///
+/// See [snapshot::render].
+///
+/// # Arguments
+///
+/// * `tag` - A string identifying the tag for which to retrieve the trace.
+///
+/// # Returns
+///
+/// Returns the color-free rendering of the trace, or `None` if no trace is found.
+pub fn get_trace_snapshot_for_tag(
+    #[cfg(feature = "trace")] tag: &str,
+    #[cfg(not(feature = "trace"))] _tag: &str,
+) -> Option<String> {
+    #[cfg(feature = "trace")]
+    {
+        TRACE_TAGS.with(|trace| trace.borrow().get_snapshot(tag))
+    }
+
+    #[cfg(not(feature = "trace"))]
+    None
+}
+
+/// Retrieves the trace for a specific tag, rendered with the given view.
+///
+/// See [view::render].
+///
+/// # Arguments
+///
+/// * `tag` - A string identifying the tag for which to retrieve the trace.
+/// * `view` - The rendering of the trace.
+///
+/// # Returns
+///
+/// Returns the rendering of the trace, or `None` if no trace is found.
+#[cfg(feature = "trace")]
+pub fn get_trace_view_for_tag(tag: &str, view: view::TraceView) -> Option<String> {
+    TRACE_TAGS.with(|trace| trace.borrow().get_trace_view(tag, view))
+}
+
+/// Lists the tags of the traces recorded in the current thread, sorted.
+#[cfg(feature = "trace")]
+pub fn list_tags() -> Vec<String> {
+    let mut tags: Vec<_> =
+        TRACE_TAGS.with(|trace| trace.borrow().tags().map(ToString::to_string).collect());
+    tags.sort();
+    tags
+}
+
+/// Removes the trace for a specific tag, returning it if it exists.
+///
+/// # Arguments
+///
+/// * `tag` - A string identifying the tag for which to remove the trace.
+#[cfg(feature = "trace")]
+pub fn remove_tag(tag: &str) -> Option<Trace> {
+    TRACE_TAGS.with(|trace| trace.borrow_mut().remove(tag))
+}
+
+/// Removes all the traces recorded in the current thread, returning them with their tags.
+///
+/// This is useful when tags are computed at runtime, for example per input file or per
+/// request, so that their traces don't accumulate.
+#[cfg(feature = "trace")]
+pub fn drain_tags() -> Vec<(Cow<'static, str>, Trace)> {
+    TRACE_TAGS.with(|trace| trace.borrow_mut().drain().collect())
+}
+
 /// Prints the trace for a specific tag.
 ///
 /// # Arguments
 ///
-/// * `tag` - A static string identifying the tag for which to print the trace.
-pub fn print_trace_for_tag(tag: &'static str) {
+/// * `tag` - A string identifying the tag for which to print the trace.
+pub fn print_trace_for_tag(tag: &str) {
     print(get_trace_for_tag(tag).unwrap_or(format!("No trace found for tag '{}'", tag)));
 }
 
+/// Prints the trace for a specific tag, rendered with the given view.
+///
+/// # Arguments
+///
+/// * `tag` - A string identifying the tag for which to print the trace.
+/// * `view` - The rendering of the trace.
+#[cfg(feature = "trace")]
+pub fn print_trace_view_for_tag(tag: &str, view: view::TraceView) {
+    print(get_trace_view_for_tag(tag, view).unwrap_or(format!("No trace found for tag '{}'", tag)));
+}
+
 /// Helper function to for unbuffered output.
 ///
 /// # Arguments
@@ -397,8 +563,11 @@ mod tests {
             let result = parser("hello world");
             assert!(result.is_ok());
 
+            // The silenced parser is summarized in a single line.
             let trace = get_trace_for_tag(DEFAULT_TAG).unwrap();
-            assert!(!trace.contains("silent_parser"));
+            assert_eq!(trace.lines().count(), 1);
+            assert!(trace.contains("silent_parser"));
+            assert!(trace.contains("silenced, 0 nested invocations hidden -> Ok"));
         }
 
         #[test]
@@ -425,7 +594,9 @@ mod tests {
 
             let trace = get_trace_for_tag(DEFAULT_TAG).unwrap();
             assert!(trace.contains("outer_parser"));
-            assert!(!trace.contains("inner_parser"));
+            assert_eq!(trace.matches("inner_parser").count(), 1);
+            assert!(trace.contains("silenced, 1 nested invocations hidden"));
+            assert!(!trace.contains("inner("));
         }
 
         #[test]
@@ -463,8 +634,9 @@ mod tests {
             assert!(trace.contains("first"));
             assert!(trace.contains("fourth"));
 
-            // Check that the silenced parts are not in the trace
-            assert!(!trace.contains("silent"));
+            // Check that the silenced parts are summarized in a single line
+            assert_eq!(trace.matches("[silent]").count(), 1);
+            assert!(trace.contains("silenced, 3 nested invocations hidden"));
             assert!(!trace.contains("second"));
             assert!(!trace.contains("inner_silent"));
             assert!(!trace.contains("third"));
@@ -489,6 +661,162 @@ mod tests {
                         .position(|&r| r.contains("fourth"))
                         .unwrap()
             );
+            assert!(
+                trace_lines
+                    .iter()
+                    .position(|&r| r.contains("[silent]"))
+                    .unwrap()
+                    < trace_lines
+                        .iter()
+                        .position(|&r| r.contains("fourth"))
+                        .unwrap()
+            );
+        }
+
+        #[test]
+        fn test_silence_tree_hidden() {
+            set_trace_config!(
+                hidden,
+                config::TraceConfig::new().silence(config::TraceSilence::Hide)
+            );
+            let mut parser = silence_tree(
+                "hidden",
+                None,
+                "silent_parser",
+                tag::<_, _, VerboseError<_>>("hello"),
+            );
+            assert!(parser("hello world").is_ok());
+            assert_eq!(get_trace_for_tag("hidden"), Some(String::new()));
+        }
+
+        #[test]
+        fn test_silence_tree_drops_buffered_events() {
+            fn parser(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+                silence_tree!(dropped, "quiet", trace!(dropped, "inner", tag("a")))(input)
+            }
+
+            let buffered = TRACE_SILENT.with(|silent| silent.borrow().events.len());
+            for silence in [config::TraceSilence::Summary, config::TraceSilence::Hide] {
+                set_trace_silence!(dropped, silence);
+                for _ in 0..3 {
+                    assert!(parser("a").is_ok());
+                    assert!(parser("b").is_err());
+                }
+                assert_eq!(
+                    TRACE_SILENT.with(|silent| silent.borrow().events.len()),
+                    buffered
+                );
+            }
+        }
+
+        #[test]
+        fn test_silence_tree_expand_on_failure() {
+            fn parser(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+                trace!(
+                    expanded,
+                    "outer",
+                    silence_tree!(expanded, "quiet", trace!(expanded, "inner", tag("a")))
+                )(input)
+            }
+
+            set_trace_silence!(expanded, config::TraceSilence::ExpandOnFailure);
+            let buffered = TRACE_SILENT.with(|silent| silent.borrow().events.len());
+
+            // On success, the silenced parser is summarized and its events are thrown away.
+            assert!(parser("a").is_ok());
+            let trace = get_trace!(expanded).unwrap();
+            assert_eq!(trace.lines().count(), 3);
+            assert!(trace.contains("silenced, 1 nested invocations hidden"));
+            assert!(!trace.contains("[inner]"));
+            assert_eq!(
+                TRACE_SILENT.with(|silent| silent.borrow().events.len()),
+                buffered
+            );
+
+            // On failure, its events are spliced into the trace at the current level.
+            reset_trace!(expanded);
+            assert!(parser("b").is_err());
+            let trace = get_trace!(expanded).unwrap();
+            let lines: Vec<_> = trace.lines().collect();
+            assert_eq!(lines.len(), 6);
+            assert!(lines[1].starts_with("| ") && lines[1].contains("[quiet]"));
+            assert!(lines[2].starts_with("| | ") && lines[2].contains("[inner]"));
+            assert!(!trace.contains("silenced"));
+        }
+    }
+
+    #[cfg(feature = "trace-memo")]
+    mod trace_memo_tests {
+        use {
+            super::*,
+            nom::{branch::alt, sequence::pair},
+            std::cell::Cell,
+        };
+
+        #[test]
+        fn test_memoize() {
+            reset_trace!();
+
+            thread_local! {
+                static CALLS: Cell<usize> = const { Cell::new(0) };
+            }
+
+            fn word(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+                CALLS.with(|calls| calls.set(calls.get() + 1));
+                tag("hello")(input)
+            }
+
+            let word = memoize(DEFAULT_TAG, None, "word", word);
+            let mut parser = alt((pair(word.clone(), tag("!")), pair(word, tag(" world"))));
+
+            let result = parser("hello world");
+            assert_eq!(result, Ok(("", ("hello", " world"))));
+            assert_eq!(CALLS.with(Cell::get), 1);
+
+            let trace = get_trace_for_tag(DEFAULT_TAG).unwrap();
+            assert_eq!(trace.matches("memo miss").count(), 1);
+            assert_eq!(trace.matches("memo hit").count(), 1);
+        }
+
+        #[test]
+        fn test_memoize_different_positions() {
+            reset_trace!();
+
+            let a = memoize::<_, _, VerboseError<&str>, _>(DEFAULT_TAG, None, "a", tag("a"));
+            let mut parser = pair(a.clone(), a);
+
+            assert_eq!(parser("aab"), Ok(("b", ("a", "a"))));
+            let trace = get_trace_for_tag(DEFAULT_TAG).unwrap();
+            assert_eq!(trace.matches("memo miss").count(), 2);
+            assert_eq!(trace.matches("memo hit").count(), 0);
+        }
+
+        #[test]
+        fn test_memoize_runtime_input() {
+            let first = String::from("aaa");
+            let second = String::from("bbb");
+            let mut parser =
+                memoize::<_, _, VerboseError<&str>, _>(DEFAULT_TAG, None, "a", tag("a"));
+
+            assert_eq!(parser(first.as_str()), Ok(("aa", "a")));
+            assert_eq!(parser(&first[1..]), Ok(("a", "a")));
+            // Another buffer at the same position doesn't get the results cached for the first.
+            assert!(parser(second.as_str()).is_err());
+        }
+
+        #[test]
+        fn test_memo_call_sites() {
+            fn keyword(input: &str) -> IResult<&str, &str, VerboseError<&str>> {
+                alt((memo!(tag("a")), memo!(tag("b"))))(input)
+            }
+
+            reset_trace!();
+            assert_eq!(keyword("b"), Ok(("", "b")));
+            assert_eq!(keyword("a"), Ok(("", "a")));
+
+            let trace = get_trace_for_tag(DEFAULT_TAG).unwrap();
+            assert_eq!(trace.matches("memo miss").count(), 3);
+            assert_eq!(trace.matches("memo hit").count(), 0);
         }
     }
 
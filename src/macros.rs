@@ -13,6 +13,116 @@ macro_rules! __fn_name {
     }};
 }
 
+/// Registers the call site of a tracing macro, from the arguments given to the macro.
+///
+/// Only constant data is registered: the context and the explicit caller name are registered if
+/// they are literals, and left out otherwise.
+#[doc(hidden)]
+#[cfg(feature = "trace-registry")]
+#[macro_export]
+macro_rules! __register_call_site {
+    ($kind:ident; $($args:tt)*) => {
+        $crate::__register_call_site!(@args $kind [] $($args)*);
+    };
+
+    // Classifies the arguments, but the parser.
+    (@args $kind:ident [$($done:tt)*] tag = $tag:expr, $($rest:tt)+) => {
+        $crate::__register_call_site!(@args $kind [$($done)* (tag)] $($rest)+);
+    };
+    (@args $kind:ident [$($done:tt)*] $arg:ident, $($rest:tt)+) => {
+        $crate::__register_call_site!(@args $kind [$($done)* (ident $arg)] $($rest)+);
+    };
+    (@args $kind:ident [$($done:tt)*] $arg:literal, $($rest:tt)+) => {
+        $crate::__register_call_site!(@args $kind [$($done)* (literal $arg)] $($rest)+);
+    };
+    (@args $kind:ident [$($done:tt)*] $arg:expr, $($rest:tt)+) => {
+        $crate::__register_call_site!(@args $kind [$($done)* (expr)] $($rest)+);
+    };
+    (@args $kind:ident [$($done:tt)*] $parser:expr $(,)?) => {
+        $crate::__register_call_site!(@site $kind $($done)*);
+    };
+
+    // Matches the classified arguments with the forms of the macros.
+    (@site $kind:ident) => {
+        $crate::__register_call_site!(@submit $kind, (), Some($crate::DEFAULT_TAG), (none));
+    };
+    (@site $kind:ident (tag)) => {
+        $crate::__register_call_site!(@submit $kind, (), None, (none));
+    };
+    (@site $kind:ident (ident $tag:ident)) => {
+        $crate::__register_call_site!(@submit $kind, (), Some(stringify!($tag)), (none));
+    };
+    (@site $kind:ident $context:tt) => {
+        $crate::__register_call_site!(@submit $kind, (), Some($crate::DEFAULT_TAG), $context);
+    };
+    (@site $kind:ident (tag) $context:tt) => {
+        $crate::__register_call_site!(@submit $kind, (), None, $context);
+    };
+    (@site $kind:ident (ident $tag:ident) $context:tt) => {
+        $crate::__register_call_site!(@submit $kind, (), Some(stringify!($tag)), $context);
+    };
+    (@site $kind:ident $caller:tt (ident $tag:ident)) => {
+        $crate::__register_call_site!(@submit $kind, $caller, Some(stringify!($tag)), (none));
+    };
+    (@site $kind:ident $caller:tt $context:tt) => {
+        $crate::__register_call_site!(@submit $kind, $caller, Some($crate::DEFAULT_TAG), $context);
+    };
+    (@site $kind:ident $caller:tt (ident $tag:ident) $context:tt) => {
+        $crate::__register_call_site!(@submit $kind, $caller, Some(stringify!($tag)), $context);
+    };
+
+    (@submit $kind:ident, $caller:tt, $tag:expr, $context:tt) => {
+        $crate::__register_call_site!(@name $caller);
+        $crate::registry::inventory::submit! {
+            $crate::registry::CallSite::new(
+                $crate::registry::CallSiteKind::$kind,
+                __nom_tracer_call_site,
+                $tag,
+                $crate::__register_call_site!(@context $context),
+                $crate::__register_call_site!(@dynamic $context),
+                module_path!(),
+                file!(),
+                line!(),
+                column!(),
+            )
+        }
+    };
+
+    (@name (literal $caller:literal)) => {
+        fn __nom_tracer_call_site() -> &'static str {
+            $caller
+        }
+    };
+    (@name $caller:tt) => {
+        fn __nom_tracer_call_site() -> &'static str {
+            $crate::__fn_name!()
+        }
+    };
+
+    (@context (literal $context:literal)) => {
+        Some($context)
+    };
+    (@context $context:tt) => {
+        None
+    };
+
+    (@dynamic (none)) => {
+        false
+    };
+    (@dynamic (literal $context:literal)) => {
+        false
+    };
+    (@dynamic $context:tt) => {
+        true
+    };
+}
+#[doc(hidden)]
+#[cfg(not(feature = "trace-registry"))]
+#[macro_export]
+macro_rules! __register_call_site {
+    ($($args:tt)*) => {};
+}
+
 /// Adds tracing to a parser.
 ///
 /// This macro wraps a parser with tracing functionality.
@@ -24,36 +134,55 @@ macro_rules! __fn_name {
 /// - `trace!(tag, parser)`: Uses a custom tag and no context.
 /// - `trace!("context", parser)`: Uses the default tag and a custom context.
 /// - `trace!(tag, "context", parser)`: Uses a custom tag and a custom context.
+/// - `trace!(tag = expr, ...)`: Uses a tag computed at runtime, given as a `String`,
+///   a `&'static str` or a `Cow<'static, str>`, instead of an identifier.
 ///
 /// When the trace feature is disabled, this macro becomes a no-op and simply returns the parser.
 #[cfg(any(feature = "trace", feature = "trace-context"))]
 #[macro_export]
 macro_rules! trace {
-    ($parser:expr $(,)?) => {
+    ($($args:tt)*) => {{
+        $crate::__register_call_site!(Trace; $($args)*);
+        $crate::__trace!($($args)*)
+    }};
+}
+#[doc(hidden)]
+#[cfg(any(feature = "trace", feature = "trace-context"))]
+#[macro_export]
+macro_rules! __trace {
+    ($parser:expr $(,)?) => {{
         $crate::tr($crate::DEFAULT_TAG, None, $crate::__fn_name!(), $parser)
-    };
+    }};
 
-    ($tag:ident, $parser:expr $(,)?) => {
+    (tag = $tag:expr, $parser:expr $(,)?) => {{
+        $crate::tr($tag, None, $crate::__fn_name!(), $parser)
+    }};
+
+    ($tag:ident, $parser:expr $(,)?) => {{
         $crate::tr(stringify!($tag), None, $crate::__fn_name!(), $parser)
-    };
+    }};
 
-    ($context:expr, $parser:expr $(,)?) => {
+    ($context:expr, $parser:expr $(,)?) => {{
         $crate::tr(
             $crate::DEFAULT_TAG,
             Some($context),
             $crate::__fn_name!(),
             $parser,
         )
-    };
+    }};
 
-    ($tag:ident, $context:expr, $parser:expr $(,)?) => {
+    (tag = $tag:expr, $context:expr, $parser:expr $(,)?) => {{
+        $crate::tr($tag, Some($context), $crate::__fn_name!(), $parser)
+    }};
+
+    ($tag:ident, $context:expr, $parser:expr $(,)?) => {{
         $crate::tr(
             stringify!($tag),
             Some($context),
             $crate::__fn_name!(),
             $parser,
         )
-    };
+    }};
 
     ($caller:expr, $tag:ident, $parser:expr $(,)?) => {{
         $crate::tr(stringify!($tag), None, $caller, $parser)
@@ -73,12 +202,18 @@ macro_rules! trace {
     ($parser:expr $(,)?) => {
         $parser
     };
+    (tag = $tag:expr, $parser:expr $(,)?) => {
+        $parser
+    };
     ($tag:ident, $parser:expr $(,)?) => {
         $parser
     };
     ($context:expr, $parser:expr $(,)?) => {
         $parser
     };
+    (tag = $tag:expr, $context:expr, $parser:expr $(,)?) => {
+        $parser
+    };
     ($tag:ident, $context:expr, $parser:expr $(,)?) => {
         $parser
     };
@@ -101,20 +236,41 @@ macro_rules! trace {
 /// This macro wraps a parser and prevents it and its sub-parsers from generating trace output.
 /// It's useful for reducing noise in the trace output for well-tested or less interesting parts of your parser.
 ///
+/// By default, the trace gets a single summary line for the silenced parser, with its input, its
+/// result, and the number of nested invocations hidden. See
+/// [set_trace_silence!](crate::set_trace_silence) to hide it completely, or to expand it when it
+/// fails.
+///
 /// # Usage
 ///
 /// - `silence_tree!(parser)`: Silences the default tag.
 /// - `silence_tree!(tag, parser)`: Silences a specific tag.
 /// - `silence_tree!("context", parser)`: Silences the default tag with a context.
 /// - `silence_tree!(tag, "context", parser)`: Silences a specific tag with a context.
+/// - `silence_tree!(tag = expr, ...)`: Uses a tag computed at runtime, given as a `String`,
+///   a `&'static str` or a `Cow<'static, str>`, instead of an identifier.
 #[cfg(feature = "trace-silencing")]
 #[macro_export]
 macro_rules! silence_tree {
+    ($($args:tt)*) => {{
+        $crate::__register_call_site!(SilenceTree; $($args)*);
+        $crate::__silence_tree!($($args)*)
+    }};
+}
+#[doc(hidden)]
+#[cfg(feature = "trace-silencing")]
+#[macro_export]
+macro_rules! __silence_tree {
     ($parser:expr $(,)?) => {{
         let caller = $crate::__fn_name!();
         $crate::silence_tree($crate::DEFAULT_TAG, None, caller, $parser)
     }};
 
+    (tag = $tag:expr, $parser:expr $(,)?) => {{
+        let caller = $crate::__fn_name!();
+        $crate::silence_tree($tag, None, caller, $parser)
+    }};
+
     ($tag:ident, $parser:expr $(,)?) => {{
         let caller = $crate::__fn_name!();
         $crate::silence_tree(stringify!($tag), None, caller, $parser)
@@ -125,6 +281,11 @@ macro_rules! silence_tree {
         $crate::silence_tree($crate::DEFAULT_TAG, Some($context), caller, $parser)
     }};
 
+    (tag = $tag:expr, $context:expr, $parser:expr $(,)?) => {{
+        let caller = $crate::__fn_name!();
+        $crate::silence_tree($tag, Some($context), caller, $parser)
+    }};
+
     ($tag:ident, $context:expr, $parser:expr $(,)?) => {{
         let caller = $crate::__fn_name!();
         $crate::silence_tree(stringify!($tag), Some($context), caller, $parser)
@@ -143,12 +304,116 @@ macro_rules! silence_tree {
     }};
 }
 
+/// Memoizes the results of a parser.
+///
+/// This macro wraps a parser with packrat memoization: results are cached by input position in
+/// the returned parser, and cache hits and misses are recorded in the trace. Clones of the
+/// returned parser share their cache, but two `memo!` wrappers never do. See [memoize](crate::memoize).
+///
+/// # Usage
+///
+/// - `memo!(parser)`: Uses the default tag and no context.
+/// - `memo!(tag, parser)`: Uses a custom tag and no context.
+/// - `memo!("context", parser)`: Uses the default tag and a custom context.
+/// - `memo!(tag, "context", parser)`: Uses a custom tag and a custom context.
+/// - `memo!(tag = expr, ...)`: Uses a tag computed at runtime, given as a `String`,
+///   a `&'static str` or a `Cow<'static, str>`, instead of an identifier.
+///
+/// When the trace-memo feature is disabled, this macro becomes a no-op and simply returns the parser.
+#[cfg(feature = "trace-memo")]
+#[macro_export]
+macro_rules! memo {
+    ($parser:expr $(,)?) => {
+        $crate::memoize($crate::DEFAULT_TAG, None, $crate::__fn_name!(), $parser)
+    };
+
+    (tag = $tag:expr, $parser:expr $(,)?) => {
+        $crate::memoize($tag, None, $crate::__fn_name!(), $parser)
+    };
+
+    ($tag:ident, $parser:expr $(,)?) => {
+        $crate::memoize(stringify!($tag), None, $crate::__fn_name!(), $parser)
+    };
+
+    ($context:expr, $parser:expr $(,)?) => {
+        $crate::memoize(
+            $crate::DEFAULT_TAG,
+            Some($context),
+            $crate::__fn_name!(),
+            $parser,
+        )
+    };
+
+    (tag = $tag:expr, $context:expr, $parser:expr $(,)?) => {
+        $crate::memoize($tag, Some($context), $crate::__fn_name!(), $parser)
+    };
+
+    ($tag:ident, $context:expr, $parser:expr $(,)?) => {
+        $crate::memoize(
+            stringify!($tag),
+            Some($context),
+            $crate::__fn_name!(),
+            $parser,
+        )
+    };
+
+    ($caller:expr, $tag:ident, $parser:expr $(,)?) => {{
+        $crate::memoize(stringify!($tag), None, $caller, $parser)
+    }};
+
+    ($caller:expr, $context:expr, $parser:expr $(,)?) => {{
+        $crate::memoize($crate::DEFAULT_TAG, Some($context), $caller, $parser)
+    }};
+
+    ($caller:expr, $tag:ident, $context:expr, $parser:expr $(,)?) => {{
+        $crate::memoize(stringify!($tag), Some($context), $caller, $parser)
+    }};
+}
+#[cfg(not(feature = "trace-memo"))]
+#[macro_export]
+macro_rules! memo {
+    ($parser:expr $(,)?) => {
+        $parser
+    };
+    (tag = $tag:expr, $parser:expr $(,)?) => {
+        $parser
+    };
+    ($tag:ident, $parser:expr $(,)?) => {
+        $parser
+    };
+    ($context:expr, $parser:expr $(,)?) => {
+        $parser
+    };
+    (tag = $tag:expr, $context:expr, $parser:expr $(,)?) => {
+        $parser
+    };
+    ($tag:ident, $context:expr, $parser:expr $(,)?) => {
+        $parser
+    };
+
+    ($caller:expr, $tag:ident, $parser:expr $(,)?) => {{
+        $parser
+    }};
+
+    ($caller:expr, $context:expr, $parser:expr $(,)?) => {{
+        $parser
+    }};
+
+    ($caller:expr, $tag:ident, $context:expr, $parser:expr $(,)?) => {{
+        $parser
+    }};
+}
+
 /// Activates tracing for a specific tag or the default tag.
 ///
 /// # Usage
 ///
 /// - `activate_trace!()`: Activates tracing for the default tag.
 /// - `activate_trace!(tag)`: Activates tracing for a specific tag.
+/// - `activate_trace!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `activate_trace!(tag = "json.*")`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
 #[cfg(feature = "trace")]
 #[macro_export]
 macro_rules! activate_trace (
@@ -157,6 +422,11 @@ macro_rules! activate_trace (
             trace.borrow_mut().activate($crate::DEFAULT_TAG);
         });
     };
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().activate(AsRef::<str>::as_ref(&$tag));
+        });
+    };
     ($tag:ident) => {
         $crate::TRACE_TAGS.with(|trace| {
             trace.borrow_mut().activate(stringify!($tag));
@@ -167,6 +437,7 @@ macro_rules! activate_trace (
 #[macro_export]
 macro_rules! activate_trace (
     () => {};
+    (tag = $tag:expr) => {};
     ($tag:ident) => {};
 );
 
@@ -176,6 +447,10 @@ macro_rules! activate_trace (
 ///
 /// - `deactivate_trace!()`: Deactivates tracing for the default tag.
 /// - `deactivate_trace!(tag)`: Deactivates tracing for a specific tag.
+/// - `deactivate_trace!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `deactivate_trace!(tag = "json.*")`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
 #[cfg(feature = "trace")]
 #[macro_export]
 macro_rules! deactivate_trace (
@@ -185,6 +460,12 @@ macro_rules! deactivate_trace (
         });
     };
 
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().deactivate(AsRef::<str>::as_ref(&$tag));
+        });
+    };
+
     ($tag:ident) => {
         $crate::TRACE_TAGS.with(|trace| {
             trace.borrow_mut().deactivate(stringify!($tag));
@@ -195,6 +476,7 @@ macro_rules! deactivate_trace (
 #[macro_export]
 macro_rules! deactivate_trace (
     () => {};
+    (tag = $tag:expr) => {};
     ($tag:ident) => {};
 );
 
@@ -204,6 +486,10 @@ macro_rules! deactivate_trace (
 ///
 /// - `activate_trace_print!()`: Activates trace printing for the default tag.
 /// - `activate_trace_print!(tag)`: Activates trace printing for a specific tag.
+/// - `activate_trace_print!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `activate_trace_print!(tag = "json.*")`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
 #[cfg(feature = "trace-print")]
 #[macro_export]
 macro_rules! activate_trace_print (
@@ -212,6 +498,11 @@ macro_rules! activate_trace_print (
             trace.borrow_mut().activate_trace_print($crate::DEFAULT_TAG);
         });
     };
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().activate_trace_print(AsRef::<str>::as_ref(&$tag));
+        });
+    };
     ($tag:ident) => {
         $crate::TRACE_TAGS.with(|trace| {
             trace.borrow_mut().activate_trace_print(stringify!($tag));
@@ -222,6 +513,7 @@ macro_rules! activate_trace_print (
 #[macro_export]
 macro_rules! activate_trace_print (
     () => {};
+    (tag = $tag:expr) => {};
     ($tag:ident) => {};
 );
 
@@ -231,6 +523,10 @@ macro_rules! activate_trace_print (
 ///
 /// - `deactivate_trace_print!()`: Deactivates trace printing for the default tag.
 /// - `deactivate_trace_print!(tag)`: Deactivates trace printing for a specific tag.
+/// - `deactivate_trace_print!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `deactivate_trace_print!(tag = "json.*")`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
 #[cfg(feature = "trace")]
 #[macro_export]
 macro_rules! deactivate_trace_print (
@@ -240,6 +536,12 @@ macro_rules! deactivate_trace_print (
         });
     };
 
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().deactivate_trace_print(AsRef::<str>::as_ref(&$tag));
+        });
+    };
+
     ($tag:ident) => {
         $crate::TRACE_TAGS.with(|trace| {
             trace.borrow_mut().deactivate_trace_print(stringify!($tag));
@@ -250,6 +552,84 @@ macro_rules! deactivate_trace_print (
 #[macro_export]
 macro_rules! deactivate_trace_print (
     () => {};
+    (tag = $tag:expr) => {};
+    ($tag:ident) => {};
+);
+
+/// Switches a specific tag or the default tag to statistics-only mode.
+///
+/// In this mode, per-location counters (calls, results, max depth and consumed bytes) are
+/// kept instead of the events, and [get_trace!](crate::get_trace) and [print_trace!](crate::print_trace) show them as a table.
+///
+/// # Usage
+///
+/// - `activate_trace_stats!()`: Switches the default tag to statistics-only mode.
+/// - `activate_trace_stats!(tag)`: Switches a specific tag to statistics-only mode.
+/// - `activate_trace_stats!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `activate_trace_stats!(tag = "json.*")`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! activate_trace_stats (
+    () => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().activate_trace_stats($crate::DEFAULT_TAG);
+        });
+    };
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().activate_trace_stats(AsRef::<str>::as_ref(&$tag));
+        });
+    };
+    ($tag:ident) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().activate_trace_stats(stringify!($tag));
+        });
+    };
+);
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! activate_trace_stats (
+    () => {};
+    (tag = $tag:expr) => {};
+    ($tag:ident) => {};
+);
+
+/// Switches a specific tag or the default tag back to recording events.
+///
+/// # Usage
+///
+/// - `deactivate_trace_stats!()`: Switches the default tag back to recording events.
+/// - `deactivate_trace_stats!(tag)`: Switches a specific tag back to recording events.
+/// - `deactivate_trace_stats!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `deactivate_trace_stats!(tag = "json.*")`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! deactivate_trace_stats (
+    () => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().deactivate_trace_stats($crate::DEFAULT_TAG);
+        });
+    };
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().deactivate_trace_stats(AsRef::<str>::as_ref(&$tag));
+        });
+    };
+    ($tag:ident) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().deactivate_trace_stats(stringify!($tag));
+        });
+    };
+);
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! deactivate_trace_stats (
+    () => {};
+    (tag = $tag:expr) => {};
     ($tag:ident) => {};
 );
 
@@ -261,6 +641,7 @@ macro_rules! deactivate_trace_print (
 ///
 /// - `reset_trace!()`: Resets the trace for the default tag.
 /// - `reset_trace!(tag)`: Resets the trace for a specific tag.
+/// - `reset_trace!(tag = expr)`: Same, with a tag computed at runtime.
 #[cfg(feature = "trace")]
 #[macro_export]
 macro_rules! reset_trace (
@@ -270,6 +651,12 @@ macro_rules! reset_trace (
         });
     };
 
+    (tag = $tag:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().clear(AsRef::<str>::as_ref(&$tag));
+        });
+    };
+
     ($tag:ident) => {
         $crate::TRACE_TAGS.with(|trace| {
             trace.borrow_mut().clear(stringify!($tag));
@@ -280,6 +667,7 @@ macro_rules! reset_trace (
 #[macro_export]
 macro_rules! reset_trace (
     () => {};
+    (tag = $tag:expr) => {};
     ($tag:ident) => {};
 );
 
@@ -292,6 +680,10 @@ macro_rules! reset_trace (
 ///
 /// - `set_max_level!(level)`: Sets the max level for the default tag.
 /// - `set_max_level!(tag, level)`: Sets the max level for a specific tag.
+/// - `set_max_level!(tag = expr, level)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `set_max_level!(tag = "json.*", level)`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
 ///
 /// The `level` parameter should be an `Option<usize>`. Use `None` to remove the limit.
 #[cfg(feature = "trace-max-level")]
@@ -302,6 +694,11 @@ macro_rules! set_max_level (
             trace.borrow_mut().panic_on_level($crate::DEFAULT_TAG, $level);
         });
     };
+    (tag = $tag:expr, $level:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().panic_on_level(AsRef::<str>::as_ref(&$tag), $level);
+        });
+    };
     ($tag:ident, $level:expr) => {
         $crate::TRACE_TAGS.with(|trace| {
             trace.borrow_mut().panic_on_level(stringify!($tag), $level);
@@ -312,15 +709,217 @@ macro_rules! set_max_level (
 #[macro_export]
 macro_rules! set_max_level (
     ($level:expr) => {};
+    (tag = $tag:expr, $level:expr) => {};
     ($tag:ident, $level:expr) => {};
 );
 
+/// Sets which events a trace keeps, as a [TraceRetention](crate::config::TraceRetention).
+///
+/// # Usage
+///
+/// - `set_trace_retention!(retention)`: Sets the retention of the default tag.
+/// - `set_trace_retention!(tag, retention)`: Sets the retention of a specific tag.
+/// - `set_trace_retention!(tag = expr, retention)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like
+/// `set_trace_retention!(tag = "json.*", retention)`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! set_trace_retention (
+    ($retention:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_retention($crate::DEFAULT_TAG, $retention);
+        });
+    };
+    (tag = $tag:expr, $retention:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_retention(AsRef::<str>::as_ref(&$tag), $retention);
+        });
+    };
+    ($tag:ident, $retention:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_retention(stringify!($tag), $retention);
+        });
+    };
+);
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! set_trace_retention (
+    ($retention:expr) => {};
+    (tag = $tag:expr, $retention:expr) => {};
+    ($tag:ident, $retention:expr) => {};
+);
+
+/// Sets the deepest nesting level recorded in a trace, as an `Option<usize>`.
+///
+/// The parsers nested deeper aren't recorded individually, but summarized in the parser at
+/// that level. See [Trace::record_depth](crate::traces::Trace::record_depth).
+///
+/// # Usage
+///
+/// - `set_record_depth!(depth)`: Sets the recording depth of the default tag.
+/// - `set_record_depth!(tag, depth)`: Sets the recording depth of a specific tag.
+/// - `set_record_depth!(tag = expr, depth)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like
+/// `set_record_depth!(tag = "json.*", depth)`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! set_record_depth (
+    ($depth:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_record_depth($crate::DEFAULT_TAG, $depth);
+        });
+    };
+    (tag = $tag:expr, $depth:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_record_depth(AsRef::<str>::as_ref(&$tag), $depth);
+        });
+    };
+    ($tag:ident, $depth:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_record_depth(stringify!($tag), $depth);
+        });
+    };
+);
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! set_record_depth (
+    ($depth:expr) => {};
+    (tag = $tag:expr, $depth:expr) => {};
+    ($tag:ident, $depth:expr) => {};
+);
+
+/// Sets how the parsers silenced with [silence_tree!](crate::silence_tree!) show in a trace, as
+/// a [TraceSilence](crate::config::TraceSilence).
+///
+/// # Usage
+///
+/// - `set_trace_silence!(silence)`: Sets the mode of the default tag.
+/// - `set_trace_silence!(tag, silence)`: Sets the mode of a specific tag.
+/// - `set_trace_silence!(tag = expr, silence)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like
+/// `set_trace_silence!(tag = "json.*", silence)`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace-silencing")]
+#[macro_export]
+macro_rules! set_trace_silence (
+    ($silence:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_silence($crate::DEFAULT_TAG, $silence);
+        });
+    };
+    (tag = $tag:expr, $silence:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_silence(AsRef::<str>::as_ref(&$tag), $silence);
+        });
+    };
+    ($tag:ident, $silence:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_silence(stringify!($tag), $silence);
+        });
+    };
+);
+#[cfg(not(feature = "trace-silencing"))]
+#[macro_export]
+macro_rules! set_trace_silence (
+    ($silence:expr) => {};
+    (tag = $tag:expr, $silence:expr) => {};
+    ($tag:ident, $silence:expr) => {};
+);
+
+/// Sets the filters deciding which parsers are recorded, as
+/// [TraceFilters](crate::filters::TraceFilters).
+///
+/// # Usage
+///
+/// - `set_trace_filters!(filters)`: Sets the filters of the default tag.
+/// - `set_trace_filters!(tag, filters)`: Sets the filters of a specific tag.
+/// - `set_trace_filters!(tag = expr, filters)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like
+/// `set_trace_filters!(tag = "json.*", filters)`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! set_trace_filters (
+    ($filters:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_filters($crate::DEFAULT_TAG, $filters);
+        });
+    };
+    (tag = $tag:expr, $filters:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_filters(AsRef::<str>::as_ref(&$tag), $filters);
+        });
+    };
+    ($tag:ident, $filters:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_filters(stringify!($tag), $filters);
+        });
+    };
+);
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! set_trace_filters (
+    ($filters:expr) => {};
+    (tag = $tag:expr, $filters:expr) => {};
+    ($tag:ident, $filters:expr) => {};
+);
+
+/// Applies all the settings of a tag at once, as a [TraceConfig](crate::config::TraceConfig).
+///
+/// # Usage
+///
+/// - `set_trace_config!(config)`: Sets the configuration of the default tag.
+/// - `set_trace_config!(tag, config)`: Sets the configuration of a specific tag.
+/// - `set_trace_config!(tag = expr, config)`: Same, with a tag computed at runtime.
+///
+/// The tag can be a pattern matching several tags, like `set_trace_config!(tag = "json.*", config)`.
+/// See [TraceTags](crate::tags::TraceTags) for the pattern syntax.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! set_trace_config (
+    ($config:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_config($crate::DEFAULT_TAG, $config);
+        });
+    };
+    (tag = $tag:expr, $config:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_config(AsRef::<str>::as_ref(&$tag), $config);
+        });
+    };
+    ($tag:ident, $config:expr) => {
+        $crate::TRACE_TAGS.with(|trace| {
+            trace.borrow_mut().set_config(stringify!($tag), $config);
+        });
+    };
+);
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! set_trace_config (
+    ($config:expr) => {};
+    (tag = $tag:expr, $config:expr) => {};
+    ($tag:ident, $config:expr) => {};
+);
+
 /// Retrieves the trace for a specific tag or the default tag.
 ///
 /// # Usage
 ///
 /// - `get_trace!()`: Gets the trace for the default tag.
 /// - `get_trace!(tag)`: Gets the trace for a specific tag.
+/// - `get_trace!(tag = expr)`: Same, with a tag computed at runtime.
+/// - `get_trace!(view = view)`: Gets the trace for the default tag, rendered with a
+///   [TraceView](crate::view::TraceView).
+/// - `get_trace!(tag, view = view)`: Same, for a specific tag.
+/// - `get_trace!(tag = expr, view = view)`: Same, with a tag computed at runtime.
+///
+/// The `view` forms are only available when the `trace` feature is enabled.
 ///
 /// # Returns
 ///
@@ -330,22 +929,168 @@ macro_rules! get_trace {
     () => {
         $crate::get_trace_for_tag($crate::DEFAULT_TAG)
     };
+    (view = $view:expr) => {
+        $crate::get_trace_view_for_tag($crate::DEFAULT_TAG, $view)
+    };
+    (tag = $tag:expr, view = $view:expr) => {
+        $crate::get_trace_view_for_tag(AsRef::<str>::as_ref(&$tag), $view)
+    };
+    ($tag:ident, view = $view:expr) => {
+        $crate::get_trace_view_for_tag(stringify!($tag), $view)
+    };
+    (tag = $tag:expr) => {
+        $crate::get_trace_for_tag(AsRef::<str>::as_ref(&$tag))
+    };
     ($tag:ident) => {
         $crate::get_trace_for_tag(stringify!($tag))
     };
 }
 
+/// Retrieves the trace for a specific tag or the default tag, rendered for snapshot tests.
+///
+/// Unlike [get_trace!](crate::get_trace), the output never contains colors and parser names are normalized,
+/// see [snapshot](crate::snapshot).
+///
+/// # Usage
+///
+/// - `get_trace_snapshot!()`: Gets the trace for the default tag.
+/// - `get_trace_snapshot!(tag)`: Gets the trace for a specific tag.
+/// - `get_trace_snapshot!(tag = expr)`: Same, with a tag computed at runtime.
+///
+/// # Returns
+///
+/// Returns an `Option<String>` containing the trace output.
+#[macro_export]
+macro_rules! get_trace_snapshot {
+    () => {
+        $crate::get_trace_snapshot_for_tag($crate::DEFAULT_TAG)
+    };
+    (tag = $tag:expr) => {
+        $crate::get_trace_snapshot_for_tag(AsRef::<str>::as_ref(&$tag))
+    };
+    ($tag:ident) => {
+        $crate::get_trace_snapshot_for_tag(stringify!($tag))
+    };
+}
+
+/// Asserts that the trace for a specific tag or the default tag matches an [insta] snapshot.
+///
+/// The trace is rendered with [get_trace_snapshot!]. A missing trace is rendered as an
+/// empty string.
+///
+/// # Usage
+///
+/// - `assert_trace_snapshot!()`: Compares the default tag against a snapshot file.
+/// - `assert_trace_snapshot!(tag)`: Compares a specific tag against a snapshot file.
+/// - `assert_trace_snapshot!(@"...")`: Compares the default tag against an inline snapshot.
+/// - `assert_trace_snapshot!(tag, @"...")`: Compares a specific tag against an inline snapshot.
+/// - `assert_trace_snapshot!(tag = expr)` and `assert_trace_snapshot!(tag = expr, @"...")`:
+///   Same, with a tag computed at runtime.
+///
+/// [insta]: https://docs.rs/insta
+#[cfg(feature = "trace-snapshot")]
+#[macro_export]
+macro_rules! assert_trace_snapshot {
+    () => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::get_trace_snapshot!().unwrap_or_default()
+        )
+    };
+    (tag = $tag:expr) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::get_trace_snapshot!(tag = $tag).unwrap_or_default()
+        )
+    };
+    ($tag:ident) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::get_trace_snapshot!($tag).unwrap_or_default()
+        )
+    };
+    (@$snapshot:literal) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::get_trace_snapshot!().unwrap_or_default(),
+            @$snapshot
+        )
+    };
+    (tag = $tag:expr, @$snapshot:literal) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::get_trace_snapshot!(tag = $tag).unwrap_or_default(),
+            @$snapshot
+        )
+    };
+    ($tag:ident, @$snapshot:literal) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::get_trace_snapshot!($tag).unwrap_or_default(),
+            @$snapshot
+        )
+    };
+}
+
+/// Runs structured assertions on the trace for a specific tag or the default tag.
+///
+/// The assertions are given as a closure receiving the
+/// [TraceAssertions](crate::assertions::TraceAssertions) of the trace.
+///
+/// # Usage
+///
+/// - `assert_trace!(|trace| { ... })`: Runs assertions on the default tag.
+/// - `assert_trace!(tag, |trace| { ... })`: Runs assertions on a specific tag.
+/// - `assert_trace!(tag = expr, |trace| { ... })`: Same, with a tag computed at runtime.
+///
+/// # Panics
+///
+/// Panics if there is no trace for the tag, or if an assertion fails.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! assert_trace {
+    ($assertions:expr $(,)?) => {
+        $crate::assertions::assert_trace_for_tag($crate::DEFAULT_TAG, $assertions)
+    };
+    (tag = $tag:expr, $assertions:expr $(,)?) => {
+        $crate::assertions::assert_trace_for_tag(AsRef::<str>::as_ref(&$tag), $assertions)
+    };
+    ($tag:ident, $assertions:expr $(,)?) => {
+        $crate::assertions::assert_trace_for_tag(stringify!($tag), $assertions)
+    };
+}
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! assert_trace {
+    ($assertions:expr $(,)?) => {};
+    (tag = $tag:expr, $assertions:expr $(,)?) => {};
+    ($tag:ident, $assertions:expr $(,)?) => {};
+}
+
 /// Prints the trace for a specific tag or the default tag.
 ///
 /// # Usage
 ///
 /// - `print_trace!()`: Prints the trace for the default tag.
 /// - `print_trace!(tag)`: Prints the trace for a specific tag.
+/// - `print_trace!(tag = expr)`: Same, with a tag computed at runtime.
+/// - `print_trace!(view = view)`: Prints the trace for the default tag, rendered with a
+///   [TraceView](crate::view::TraceView).
+/// - `print_trace!(tag, view = view)`: Same, for a specific tag.
+/// - `print_trace!(tag = expr, view = view)`: Same, with a tag computed at runtime.
+///
+/// The `view` forms are only available when the `trace` feature is enabled.
 #[macro_export]
 macro_rules! print_trace {
     () => {
         $crate::print_trace_for_tag($crate::DEFAULT_TAG);
     };
+    (view = $view:expr) => {
+        $crate::print_trace_view_for_tag($crate::DEFAULT_TAG, $view);
+    };
+    (tag = $tag:expr, view = $view:expr) => {
+        $crate::print_trace_view_for_tag(AsRef::<str>::as_ref(&$tag), $view);
+    };
+    ($tag:ident, view = $view:expr) => {
+        $crate::print_trace_view_for_tag(stringify!($tag), $view);
+    };
+    (tag = $tag:expr) => {
+        $crate::print_trace_for_tag(AsRef::<str>::as_ref(&$tag));
+    };
     ($tag:ident) => {
         $crate::print_trace_for_tag(stringify!($tag));
     };
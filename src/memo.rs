@@ -0,0 +1,137 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+use {nom::IResult, std::collections::HashMap};
+
+/// Caches the results of a memoized parser for packrat memoization.
+///
+/// Results are keyed by the offset of their input from the start input, the longest input the
+/// table was used with. The table keeps the start input, so the buffer it points to can't be
+/// freed or modified while results are cached for it. When the parser is invoked on an input
+/// from another buffer, the cached results are dropped and the new input becomes the start input.
+pub struct MemoTable<I, O, E> {
+    start: Option<I>,
+    entries: HashMap<usize, IResult<I, O, E>>,
+}
+
+impl<I, O, E> Default for MemoTable<I, O, E> {
+    fn default() -> Self {
+        Self {
+            start: None,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<I, O, E> MemoTable<I, O, E>
+where
+    I: AsRef<str> + Clone,
+    O: Clone,
+    E: Clone,
+{
+    /// Returns the offset of the input from the start input.
+    ///
+    /// If the input starts before the start input in the same buffer, it becomes the start
+    /// input, and the cached results are kept. If it is from another buffer, the cached results
+    /// are dropped.
+    pub fn offset(&mut self, input: &I) -> usize {
+        let (address, end) = span(input.as_ref());
+
+        if let Some(start) = &self.start {
+            let (start_address, start_end) = span(start.as_ref());
+            if end == start_end && address >= start_address {
+                return address - start_address;
+            }
+
+            if end == start_end {
+                let shift = start_address - address;
+                self.entries = self
+                    .entries
+                    .drain()
+                    .map(|(offset, result)| (offset + shift, result))
+                    .collect();
+            } else {
+                self.entries.clear();
+            }
+        }
+
+        self.start = Some(input.clone());
+        0
+    }
+
+    /// Returns a copy of the result cached at the given offset, if any.
+    pub fn get(&self, offset: usize) -> Option<IResult<I, O, E>> {
+        self.entries.get(&offset).cloned()
+    }
+
+    /// Caches the result of the parser at the given offset.
+    pub fn insert(&mut self, offset: usize, result: IResult<I, O, E>) {
+        self.entries.insert(offset, result);
+    }
+
+    /// Removes all cached results, and forgets the start input.
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.entries.clear();
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no result is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Returns the addresses of the start and the end of a string.
+fn span(input: &str) -> (usize, usize) {
+    let address = input.as_ptr() as usize;
+    (address, address + input.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, nom::error::VerboseError};
+
+    type Table<'a> = MemoTable<&'a str, &'a str, VerboseError<&'a str>>;
+
+    #[test]
+    fn test_memo_table_insert_get() {
+        let input = String::from("hello world");
+        let mut table = Table::default();
+
+        let offset = table.offset(&input.as_str());
+        assert_eq!(offset, 0);
+        assert!(table.get(offset).is_none());
+
+        table.insert(offset, Ok((&input[5..], &input[..5])));
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get(offset), Some(Ok((" world", "hello"))));
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_memo_table_offset() {
+        let input = String::from("hello world");
+        let mut table = Table::default();
+
+        assert_eq!(table.offset(&&input[5..]), 0);
+        assert_eq!(table.offset(&&input[6..]), 1);
+        table.insert(1, Ok((&input[11..], &input[6..])));
+
+        // An earlier position in the same buffer keeps the cached results.
+        assert_eq!(table.offset(&input.as_str()), 0);
+        assert_eq!(table.offset(&&input[6..]), 6);
+        assert_eq!(table.get(6), Some(Ok(("", "world"))));
+
+        // Another buffer drops them, even with the same length.
+        let other = String::from("hello earth");
+        assert_eq!(table.offset(&&other[6..]), 0);
+        assert!(table.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Static registry of traced parsers.
+//!
+//! With the `trace-registry` feature, every [trace!](crate::trace) and
+//! [silence_tree!](macro@crate::silence_tree) call site registers itself at startup, whether it is
+//! ever invoked or not. This lists every instrumented parser of a program, and combined with
+//! [coverage](crate::coverage), finds the ones that were never invoked.
+//!
+//! Registration happens in a static, so only constant data is registered: the contexts and the
+//! explicit caller names given to these macros are registered if they are literals, and left out
+//! otherwise. A call site given an explicit caller that isn't a literal is registered with the
+//! name of the function it is in.
+
+#[cfg(feature = "trace-coverage")]
+use crate::coverage::Coverage;
+#[doc(hidden)]
+pub use inventory;
+
+const NAME_SUFFIX: &str = "::__nom_tracer_call_site";
+
+/// The macro used at a call site.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CallSiteKind {
+    /// A [trace!](crate::trace) call site.
+    Trace,
+    /// A [silence_tree!](macro@crate::silence_tree) call site.
+    SilenceTree,
+}
+
+/// Describes a call site of a tracing macro.
+#[derive(Debug)]
+pub struct CallSite {
+    /// The macro used at this call site.
+    pub kind: CallSiteKind,
+    /// The tag of the traced parser, or `None` if it is computed at runtime.
+    pub tag: Option<&'static str>,
+    /// The context of the traced parser, if it is given as a literal.
+    pub context: Option<&'static str>,
+    /// The module where the macro is used.
+    pub module_path: &'static str,
+    /// The file where the macro is used.
+    pub file: &'static str,
+    /// The line where the macro is used.
+    pub line: u32,
+    /// The column where the macro is used.
+    pub column: u32,
+    /// Whether the context is computed at runtime, so it isn't registered.
+    pub dynamic_context: bool,
+    name: fn() -> &'static str,
+}
+
+inventory::collect!(CallSite);
+
+impl CallSite {
+    #[doc(hidden)]
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(
+        kind: CallSiteKind,
+        name: fn() -> &'static str,
+        tag: Option<&'static str>,
+        context: Option<&'static str>,
+        dynamic_context: bool,
+        module_path: &'static str,
+        file: &'static str,
+        line: u32,
+        column: u32,
+    ) -> Self {
+        Self {
+            kind,
+            tag,
+            context,
+            module_path,
+            file,
+            line,
+            column,
+            name,
+            dynamic_context,
+        }
+    }
+
+    /// Returns the name of the traced parser, as recorded in the trace events' `location`.
+    ///
+    /// This is the explicit caller given to the macro if it is a literal, or else the name of
+    /// the function the macro is used in.
+    pub fn name(&self) -> &'static str {
+        let name = (self.name)();
+        name.strip_suffix(NAME_SUFFIX).unwrap_or(name)
+    }
+}
+
+/// Iterates over all the registered call sites, in no particular order.
+pub fn call_sites() -> impl Iterator<Item = &'static CallSite> {
+    inventory::iter::<CallSite>.into_iter()
+}
+
+/// Iterates over the registered call sites that were never invoked according to a coverage.
+///
+/// A call site whose context isn't a literal counts as invoked if its parser was invoked with
+/// any context.
+#[cfg(feature = "trace-coverage")]
+pub fn never_invoked(coverage: &Coverage) -> impl Iterator<Item = &'static CallSite> + '_ {
+    call_sites().filter(|site| {
+        if site.dynamic_context {
+            !coverage
+                .entries
+                .keys()
+                .any(|(name, _)| *name == site.name())
+        } else {
+            !coverage.entries.contains_key(&(site.name(), site.context))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        nom::{bytes::complete::tag, IResult},
+    };
+
+    fn registered_parser(input: &str) -> IResult<&str, &str> {
+        crate::trace!(registry_tag, "registered context", tag("a"))(input)
+    }
+
+    fn runtime_context() -> &'static str {
+        "runtime context"
+    }
+
+    fn runtime_context_parser(input: &str) -> IResult<&str, &str> {
+        crate::trace!(runtime_context(), tag("a"))(input)
+    }
+
+    #[cfg(feature = "trace-silencing")]
+    #[allow(dead_code)]
+    fn never_called_parser(input: &str) -> IResult<&str, &str> {
+        crate::silence_tree!(tag("b"))(input)
+    }
+
+    #[test]
+    fn test_call_sites() {
+        let _ = registered_parser("a");
+
+        let site = call_sites()
+            .find(|site| site.context == Some("registered context"))
+            .unwrap();
+        assert_eq!(site.kind, CallSiteKind::Trace);
+        assert_eq!(site.name(), "registered_parser");
+        assert_eq!(site.tag, Some("registry_tag"));
+        assert_eq!(site.module_path, module_path!());
+        assert_eq!(site.file, file!());
+    }
+
+    #[test]
+    fn test_runtime_context_call_sites() {
+        assert!(runtime_context_parser("a").is_ok());
+
+        let site = call_sites()
+            .find(|site| site.name() == "runtime_context_parser")
+            .unwrap();
+        assert_eq!(site.tag, Some(crate::DEFAULT_TAG));
+        assert_eq!(site.context, None);
+        assert!(site.dynamic_context);
+    }
+
+    #[cfg(feature = "trace-silencing")]
+    #[test]
+    fn test_silence_tree_call_sites() {
+        let site = call_sites()
+            .find(|site| site.name() == "never_called_parser")
+            .unwrap();
+        assert_eq!(site.kind, CallSiteKind::SilenceTree);
+        assert_eq!(site.tag, Some(crate::DEFAULT_TAG));
+    }
+
+    #[cfg(all(feature = "trace-coverage", feature = "trace-silencing"))]
+    #[test]
+    fn test_never_invoked() {
+        let mut coverage = Coverage::new();
+        coverage.record::<_, _, ()>(
+            "registered_parser",
+            Some("registered context"),
+            &Ok(("", "a")),
+        );
+
+        coverage.record::<_, _, ()>(
+            "runtime_context_parser",
+            Some("runtime context"),
+            &Ok(("", "a")),
+        );
+
+        let names: Vec<_> = never_invoked(&coverage).map(CallSite::name).collect();
+        assert!(names.contains(&"never_called_parser"));
+        assert!(!names.contains(&"registered_parser"));
+        assert!(!names.contains(&"runtime_context_parser"));
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scoped tracing sessions.
+//!
+//! The thread-local tracing state lives as long as its thread. Since test harnesses reuse
+//! threads, events recorded by an earlier test are still there when the next one starts.
+//! A [TraceSession] installs a fresh state for its own lifetime, and restores the previous
+//! one when it ends.
+
+#[cfg(feature = "trace-silencing")]
+use crate::{traces::Trace, TRACE_SILENT, TREE_SILENCE_LEVELS};
+use {
+    crate::{tags::TraceTags, TRACE_TAGS},
+    std::marker::PhantomData,
+};
+
+/// The thread-local tracing state.
+struct State {
+    tags: TraceTags,
+    #[cfg(feature = "trace-silencing")]
+    silent: Trace,
+    #[cfg(feature = "trace-silencing")]
+    silence_levels: Vec<usize>,
+}
+
+impl State {
+    /// Creates a fresh state recording into the given tags.
+    fn new(tags: TraceTags) -> Self {
+        Self {
+            tags,
+            #[cfg(feature = "trace-silencing")]
+            silent: Trace::default(),
+            #[cfg(feature = "trace-silencing")]
+            silence_levels: Vec::new(),
+        }
+    }
+
+    /// Installs this state in the current thread, returning the one it replaces.
+    fn swap(self) -> State {
+        State {
+            tags: TRACE_TAGS.with(|tags| tags.replace(self.tags)),
+            #[cfg(feature = "trace-silencing")]
+            silent: TRACE_SILENT.with(|trace| trace.replace(self.silent)),
+            #[cfg(feature = "trace-silencing")]
+            silence_levels: TREE_SILENCE_LEVELS.with(|levels| levels.replace(self.silence_levels)),
+        }
+    }
+}
+
+/// A guard giving the current thread a fresh tracing state for its lifetime.
+///
+/// While the session is alive, the tracing macros record into its own [TraceTags] and silenced
+/// trees. When it is [finished](TraceSession::finish), the captured traces are handed back and
+/// the previous state is restored. Dropping the session restores
+/// the previous state too, and hands the captured traces to the [on_drop](TraceSession::on_drop)
+/// callback, if any, for example to print them when a test panics.
+///
+/// Sessions can be nested, but must end in the reverse order they were started in.
+///
+/// ```
+/// use nom_tracer::{session::TraceSession, trace, DEFAULT_TAG};
+/// use nom::{bytes::complete::tag, IResult};
+///
+/// fn hello(input: &str) -> IResult<&str, &str> {
+///     trace!(tag("hello"))(input)
+/// }
+///
+/// let session = TraceSession::start();
+/// hello("hello world").unwrap();
+/// let tags = session.finish();
+///
+/// assert_eq!(tags.traces[DEFAULT_TAG].events.len(), 2);
+/// ```
+pub struct TraceSession {
+    previous: Option<State>,
+    on_drop: Option<Box<dyn FnOnce(TraceTags)>>,
+    // The session swaps thread-local state, so it must be finished on the thread it started on.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl TraceSession {
+    /// Starts a session with a fresh [TraceTags], as created by [TraceTags::new].
+    pub fn start() -> Self {
+        Self::with_tags(TraceTags::new())
+    }
+
+    /// Starts a session with the given [TraceTags].
+    ///
+    /// This allows configuring the session beforehand, for example to activate real-time
+    /// printing or a maximum nesting level for some tags.
+    pub fn with_tags(tags: TraceTags) -> Self {
+        Self {
+            previous: Some(State::new(tags).swap()),
+            on_drop: None,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Sets a function receiving the captured traces if the session is dropped without being
+    /// finished.
+    ///
+    /// ```
+    /// use nom_tracer::{session::TraceSession, DEFAULT_TAG};
+    ///
+    /// let _session = TraceSession::start().on_drop(|tags| {
+    ///     if std::thread::panicking() {
+    ///         eprintln!("{}", tags.traces[DEFAULT_TAG]);
+    ///     }
+    /// });
+    /// ```
+    pub fn on_drop(mut self, f: impl FnOnce(TraceTags) + 'static) -> Self {
+        self.on_drop = Some(Box::new(f));
+        self
+    }
+
+    /// Runs a function with the traces captured so far by this session.
+    pub fn with<R>(&self, f: impl FnOnce(&TraceTags) -> R) -> R {
+        TRACE_TAGS.with(|tags| f(&tags.borrow()))
+    }
+
+    /// Ends the session, restoring the previous tracing state.
+    ///
+    /// # Returns
+    ///
+    /// The traces captured during the session.
+    pub fn finish(mut self) -> TraceTags {
+        self.restore().map(|state| state.tags).unwrap_or_default()
+    }
+
+    fn restore(&mut self) -> Option<State> {
+        self.previous.take().map(State::swap)
+    }
+}
+
+impl Drop for TraceSession {
+    fn drop(&mut self) {
+        if let (Some(state), Some(on_drop)) = (self.restore(), self.on_drop.take()) {
+            on_drop(state.tags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{get_trace, trace, DEFAULT_TAG},
+        nom::{bytes::complete::tag, IResult},
+    };
+
+    fn hello(input: &str) -> IResult<&str, &str> {
+        trace!(tag("hello"))(input)
+    }
+
+    #[test]
+    fn test_session_isolates_traces() {
+        let _ = hello("hello");
+        let before = get_trace!().unwrap();
+        assert!(!before.is_empty());
+
+        let session = TraceSession::start();
+        assert!(get_trace!().unwrap().is_empty());
+
+        let _ = hello("hello");
+        assert_eq!(
+            session.with(|tags| tags.traces[DEFAULT_TAG].events.len()),
+            2
+        );
+
+        let tags = session.finish();
+        assert_eq!(tags.traces[DEFAULT_TAG].events.len(), 2);
+        assert_eq!(get_trace!().unwrap(), before);
+    }
+
+    #[test]
+    fn test_nested_sessions_and_drop() {
+        let outer = TraceSession::start();
+        let _ = hello("hello");
+
+        {
+            let mut tags = TraceTags::new();
+            tags.deactivate(DEFAULT_TAG);
+            let _inner = TraceSession::with_tags(tags);
+
+            let _ = hello("hello");
+            assert!(get_trace!().unwrap().is_empty());
+        }
+
+        let tags = outer.finish();
+        assert_eq!(tags.traces[DEFAULT_TAG].events.len(), 2);
+    }
+
+    #[test]
+    fn test_on_drop() {
+        let captured = std::rc::Rc::new(std::cell::Cell::new(0));
+
+        {
+            let captured = captured.clone();
+            let _session = TraceSession::start()
+                .on_drop(move |tags| captured.set(tags.traces[DEFAULT_TAG].events.len()));
+            let _ = hello("hello");
+        }
+        assert_eq!(captured.get(), 2);
+
+        // A finished session hands its traces back to the caller instead.
+        let session = TraceSession::start().on_drop(|_| unreachable!());
+        let _ = hello("hello");
+        assert_eq!(session.finish().traces[DEFAULT_TAG].events.len(), 2);
+    }
+}
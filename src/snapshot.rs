@@ -0,0 +1,120 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Deterministic trace rendering for snapshot tests.
+//!
+//! The regular rendering of a [Trace] depends on the enabled features: with `trace-color`,
+//! it contains ANSI escape codes. The snapshot rendering never contains colors, escapes
+//! control characters in the input, and normalizes the parser names generated by the
+//! tracing macros, so it stays the same across feature sets and refactorings.
+//!
+//! With the `trace-snapshot` feature, `assert_trace_snapshot!` compares it against an
+//! [insta](https://insta.rs) snapshot.
+
+use crate::traces::Trace;
+#[cfg(feature = "trace-snapshot")]
+#[doc(hidden)]
+pub use insta;
+
+/// Normalizes a parser name recorded by the tracing macros.
+///
+/// Closure segments (`{{closure}}`) are removed, as well as the path of the enclosing
+/// functions, leaving the name of the innermost named function.
+///
+/// ```
+/// use nom_tracer::snapshot::normalize_location;
+///
+/// assert_eq!(normalize_location("parser::{{closure}}"), "parser");
+/// assert_eq!(normalize_location("outer::inner"), "inner");
+/// ```
+pub fn normalize_location(location: &str) -> &str {
+    location
+        .rsplit("::")
+        .find(|segment| *segment != "{{closure}}")
+        .unwrap_or(location)
+}
+
+/// Renders a trace for a snapshot test.
+///
+/// Each event is rendered on its own line, indented by its nesting level, without colors.
+/// Statistics-only traces are rendered as their statistics table.
+pub fn render(trace: &Trace) -> String {
+    if let Some(stats) = &trace.stats {
+        return stats.to_string();
+    }
+
+    let mut output = String::new();
+    if trace.dropped > 0 {
+        output.push_str(&format!("({} events dropped)\n", trace.dropped));
+    }
+    for event in trace.events.iter() {
+        let mut event = event.clone();
+        event.location = normalize_location(event.location);
+        event.input = event.input.escape_debug().to_string();
+
+        output.push_str(&"| ".repeat(event.level));
+        output.push_str(&event.plain());
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{get_trace_snapshot, reset_trace, trace},
+        nom::{bytes::complete::tag, sequence::pair, IResult},
+    };
+
+    fn greeting(input: &str) -> IResult<&str, (&str, &str)> {
+        trace!(
+            "greeting",
+            pair(|i| trace!(tag("hello"))(i), |i| trace!(tag(" world"))(i))
+        )(input)
+    }
+
+    #[test]
+    fn test_normalize_location() {
+        assert_eq!(normalize_location("parser"), "parser");
+        assert_eq!(normalize_location("parser::{{closure}}"), "parser");
+        assert_eq!(
+            normalize_location("parser::{{closure}}::{{closure}}"),
+            "parser"
+        );
+        assert_eq!(normalize_location("outer::inner::{{closure}}"), "inner");
+        assert_eq!(normalize_location("{{closure}}"), "{{closure}}");
+    }
+
+    #[test]
+    fn test_render() {
+        reset_trace!();
+        let _ = greeting("hello\nworld");
+
+        assert_eq!(
+            get_trace_snapshot!().unwrap(),
+            "greeting(\"hello\\nworld\")[greeting]\n\
+             | greeting(\"hello\\nworld\")\n\
+             | -> Ok(\"hello\")\n\
+             | greeting(\"\\nworld\")\n\
+             | -> Error(Error { input: \"\\nworld\", code: Tag })\n\
+             -> Error(Error { input: \"\\nworld\", code: Tag })[greeting]\n"
+        );
+    }
+
+    #[cfg(feature = "trace-snapshot")]
+    #[test]
+    fn test_assert_trace_snapshot() {
+        reset_trace!();
+        let _ = greeting("hello world");
+
+        crate::assert_trace_snapshot!(@r#"
+        greeting("hello world")[greeting]
+        | greeting("hello world")
+        | -> Ok("hello")
+        | greeting(" world")
+        | -> Ok(" world")
+        -> Ok(("hello", " world"))[greeting]
+        "#);
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+use {
+    nom::IResult,
+    std::{
+        collections::BTreeMap,
+        fmt::{Display, Formatter},
+    },
+};
+
+/// Invocation counters for a single parser location.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LocationStats {
+    /// The number of times the parser was invoked.
+    pub calls: usize,
+    /// The number of times the parser returned `Ok`.
+    pub ok: usize,
+    /// The number of times the parser returned an `Error`.
+    pub error: usize,
+    /// The number of times the parser returned a `Failure`.
+    pub failure: usize,
+    /// The number of times the parser returned `Incomplete`.
+    pub incomplete: usize,
+    /// The deepest nesting level the parser was invoked at.
+    pub max_depth: usize,
+    /// The total number of bytes consumed by the successful invocations of the parser.
+    pub consumed: usize,
+}
+
+/// Statistics of a trace, recorded instead of its events.
+///
+/// The memory used by statistics only grows with the number of distinct parser locations,
+/// not with the number of invocations, so they can be kept enabled on long-running processes.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TraceStats {
+    /// The counters of each parser location, sorted by location.
+    pub locations: BTreeMap<&'static str, LocationStats>,
+}
+
+impl TraceStats {
+    /// Records the invocation of a parser at the given nesting level.
+    pub fn open(&mut self, location: &'static str, level: usize) {
+        let stats = self.locations.entry(location).or_default();
+        stats.calls += 1;
+        stats.max_depth = stats.max_depth.max(level);
+    }
+
+    /// Records the result of a parser.
+    pub fn close<I: AsRef<str>, O, E>(
+        &mut self,
+        location: &'static str,
+        input: &I,
+        result: &IResult<I, O, E>,
+    ) {
+        let stats = self.locations.entry(location).or_default();
+        match result {
+            Ok((rest, _)) => {
+                stats.ok += 1;
+                stats.consumed += input.as_ref().len().saturating_sub(rest.as_ref().len());
+            }
+            Err(nom::Err::Error(_)) => stats.error += 1,
+            Err(nom::Err::Failure(_)) => stats.failure += 1,
+            Err(nom::Err::Incomplete(_)) => stats.incomplete += 1,
+        }
+    }
+
+    /// Removes all the recorded statistics.
+    pub fn clear(&mut self) {
+        self.locations.clear();
+    }
+}
+
+impl Display for TraceStats {
+    /// Formats the statistics as a table, one line per parser location.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .locations
+            .keys()
+            .map(|location| location.len())
+            .chain(Some("location".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10}  {:>9}  {:>10}",
+            "location", "calls", "ok", "error", "failure", "incomplete", "max depth", "consumed"
+        )?;
+
+        for (location, stats) in self.locations.iter() {
+            writeln!(
+                f,
+                "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10}  {:>9}  {:>10}",
+                location,
+                stats.calls,
+                stats.ok,
+                stats.error,
+                stats.failure,
+                stats.incomplete,
+                stats.max_depth,
+                stats.consumed
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_stats() {
+        let mut stats = TraceStats::default();
+
+        stats.open("outer", 0);
+        stats.open("inner", 1);
+        stats.close::<_, _, ()>("inner", &"abc", &Ok(("c", "ab")));
+        stats.open("inner", 1);
+        stats.close::<_, &str, _>("inner", &"c", &Err(nom::Err::Error(())));
+        stats.close::<_, _, ()>("outer", &"abc", &Ok(("c", "ab")));
+
+        assert_eq!(
+            stats.locations["inner"],
+            LocationStats {
+                calls: 2,
+                ok: 1,
+                error: 1,
+                max_depth: 1,
+                consumed: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.locations["outer"].calls, 1);
+        assert_eq!(stats.locations["outer"].max_depth, 0);
+
+        let table = stats.to_string();
+        assert!(table.starts_with("location"));
+        assert_eq!(table.lines().count(), 3);
+
+        stats.clear();
+        assert!(stats.locations.is_empty());
+    }
+}
@@ -1,20 +1,107 @@
 // Copyright (c) Hexbee
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "trace-env")]
+use crate::env::{self, TraceDirective, TraceSpecError, TRACE_ENV};
+#[cfg(feature = "trace-silencing")]
+use crate::{config::TraceSilence, events::TraceEvent};
 use {
-    crate::{traces::Trace, DEFAULT_TAG},
+    crate::{
+        config::{SavedConfig, TraceCapture, TraceConfig, TraceRetention},
+        failure::FurthestFailure,
+        filters::TraceFilters,
+        stats::TraceStats,
+        traces::Trace,
+        view::TraceView,
+        DEFAULT_TAG,
+    },
     nom::IResult,
-    std::{collections::HashMap, fmt::Debug},
+    std::{borrow::Cow, collections::HashMap, fmt::Debug},
 };
 
 /// Manages multiple traces, each associated with a unique tag.
 ///
 /// This struct allows for organizing and managing multiple trace instances,
-/// each identified by a static string tag. It provides methods for manipulating
-/// traces, such as activating, deactivating, and resetting them.
+/// each identified by a tag. Tags are usually static strings, but can also be computed
+/// at runtime, for example to trace each input file or request separately. It provides
+/// methods for manipulating traces, such as activating, deactivating, and resetting them.
+///
+/// Tags can form a namespace, with segments separated by dots (`json.string`, `http.header`).
+/// The settings methods accept patterns, where a `*` segment matches any single segment, and
+/// a trailing `*` segment matches any number of them: `json.*` matches `json`, `json.string`
+/// and `json.number.float`, and `*` matches every tag. A pattern applies to the existing
+/// tags it matches, and to the ones created afterward.
 #[derive(Default)]
 pub struct TraceTags {
-    pub traces: HashMap<&'static str, Trace>,
+    pub traces: HashMap<Cow<'static, str>, Trace>,
+    /// The settings applied with a pattern, in the order they were applied.
+    rules: Vec<(String, Rule)>,
+    /// Whether the finished top-level parses are forwarded to the installed
+    /// [TraceCollector](crate::collector::TraceCollector). Only the thread-local traces are.
+    pub(crate) collect: bool,
+}
+
+/// A setting applied to the tags matching a pattern.
+#[derive(Clone)]
+pub(crate) enum Rule {
+    Config(TraceConfig),
+    Active(bool),
+    #[cfg(feature = "trace-print")]
+    Print(bool),
+    Capture(TraceCapture),
+    #[cfg(feature = "trace-max-level")]
+    MaxLevel(Option<usize>),
+    Retention(TraceRetention),
+    Filters(TraceFilters),
+    RecordDepth(Option<usize>),
+    #[cfg(feature = "trace-silencing")]
+    Silence(TraceSilence),
+}
+
+impl Rule {
+    fn apply(&self, trace: &mut Trace) {
+        match *self {
+            Rule::Config(ref config) => config.apply(trace),
+            Rule::Active(active) => trace.active = active,
+            #[cfg(feature = "trace-print")]
+            Rule::Print(print) => trace.print = print,
+            Rule::Capture(capture) => capture.apply(trace),
+            #[cfg(feature = "trace-max-level")]
+            Rule::MaxLevel(level) => trace.panic_on_level = level,
+            Rule::Retention(retention) => trace.set_retention(retention),
+            Rule::Filters(ref filters) => trace.filters = filters.clone(),
+            Rule::RecordDepth(depth) => trace.record_depth = depth,
+            #[cfg(feature = "trace-silencing")]
+            Rule::Silence(silence) => trace.silence = silence,
+        }
+    }
+}
+
+/// Returns `true` if the tag contains a `*` segment, and is therefore a pattern.
+pub fn is_pattern(tag: &str) -> bool {
+    tag.split('.').any(|segment| segment == "*")
+}
+
+/// Returns `true` if the tag matches the pattern.
+///
+/// A `*` segment matches any single segment, and a trailing `*` segment matches any number
+/// of segments, including none. Without any `*` segment, the tag must be equal to the pattern.
+pub fn matches_tag(pattern: &str, tag: &str) -> bool {
+    let mut segments = tag.split('.');
+    let mut patterns = pattern.split('.').peekable();
+
+    while let Some(expected) = patterns.next() {
+        if expected == "*" && patterns.peek().is_none() {
+            return true;
+        }
+
+        match segments.next() {
+            Some(segment) if expected == "*" || expected == segment => {}
+            _ => return false,
+        }
+    }
+
+    segments.next().is_none()
 }
 
 impl TraceTags {
@@ -23,74 +110,328 @@ impl TraceTags {
     /// The default trace is associated with the [DEFAULT_TAG].
     pub fn new() -> Self {
         let mut traces = HashMap::new();
-        traces.insert(DEFAULT_TAG, Trace::default());
+        traces.insert(Cow::Borrowed(DEFAULT_TAG), Trace::default());
+
+        TraceTags {
+            traces,
+            rules: Vec::new(),
+            collect: false,
+        }
+    }
+
+    /// Creates a new `TraceTags` instance configured from the [TRACE_ENV] environment variable.
+    ///
+    /// If the variable holds an invalid specification, a warning is printed on the standard
+    /// error, and the variable is ignored. See [the env module](mod@crate::env) for the syntax.
+    #[cfg(feature = "trace-env")]
+    pub fn from_env() -> Self {
+        let mut tags = Self::new();
+        if let Ok(spec) = std::env::var(TRACE_ENV) {
+            if let Err(e) = tags.apply_spec(&spec) {
+                eprintln!("nom-tracer: ignoring {}: {}", TRACE_ENV, e);
+            }
+        }
+        tags
+    }
+
+    /// Applies a trace specification, like `json:print,http:max=64,-default`.
+    ///
+    /// The specification is applied only if all its directives are valid. See
+    /// [the env module](mod@crate::env) for the syntax.
+    #[cfg(feature = "trace-env")]
+    pub fn apply_spec(&mut self, spec: &str) -> Result<(), TraceSpecError> {
+        for directive in env::parse(spec)? {
+            self.apply_directive(&directive);
+        }
+        Ok(())
+    }
+
+    /// Applies a single directive of a trace specification.
+    #[cfg(feature = "trace-env")]
+    pub fn apply_directive(&mut self, directive: &TraceDirective) {
+        let tag = directive.tag.as_str();
+        self.configure(tag, Rule::Active(directive.active));
+
+        #[cfg(feature = "trace-print")]
+        if let Some(print) = directive.print {
+            self.configure(tag, Rule::Print(print));
+        }
+        #[cfg(feature = "trace-max-level")]
+        if let Some(level) = directive.max_level {
+            self.configure(tag, Rule::MaxLevel(Some(level)));
+        }
+        if let Some(format) = directive.format {
+            self.configure(tag, Rule::Capture(format));
+        }
+    }
+
+    /// Returns the trace associated with the given tag, creating it if it doesn't exist.
+    ///
+    /// A new trace gets the settings applied so far with the patterns it matches.
+    pub fn trace_mut(&mut self, tag: impl Into<Cow<'static, str>>) -> &mut Trace {
+        self.entry(&tag.into())
+    }
+
+    /// Returns the trace associated with the given tag, inserting the tag as is if it doesn't
+    /// exist, so that static tags stay borrowed.
+    #[allow(clippy::ptr_arg)]
+    fn entry(&mut self, tag: &Cow<'static, str>) -> &mut Trace {
+        if !self.traces.contains_key(tag.as_ref()) {
+            let trace = self.new_trace(tag);
+            self.traces.insert(tag.clone(), trace);
+        }
+        self.traces
+            .get_mut(tag.as_ref())
+            .expect("the trace was just inserted")
+    }
+
+    /// Iterates over the existing tags, in no particular order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.traces.keys().map(AsRef::as_ref)
+    }
+
+    /// Removes the trace associated with the given tag.
+    ///
+    /// Returns the removed trace, or `None` if the tag doesn't exist.
+    pub fn remove(&mut self, tag: &str) -> Option<Trace> {
+        self.traces.remove(tag)
+    }
+
+    /// Removes all the traces, returning them with their tags, in no particular order.
+    pub fn drain(&mut self) -> impl Iterator<Item = (Cow<'static, str>, Trace)> + '_ {
+        self.traces.drain()
+    }
+
+    /// Creates a trace with the settings applied so far with the patterns the tag matches.
+    fn new_trace(&self, tag: &str) -> Trace {
+        let mut trace = Trace::default();
+        for (pattern, rule) in &self.rules {
+            if matches_tag(pattern, tag) {
+                rule.apply(&mut trace);
+            }
+        }
+        trace
+    }
+
+    /// Returns the settings of the given tag.
+    ///
+    /// If the tag doesn't exist, returns the settings it would be created with, without
+    /// creating it.
+    pub fn config(&self, tag: &str) -> TraceConfig {
+        match self.traces.get(tag) {
+            Some(trace) => TraceConfig::of(trace),
+            None => TraceConfig::of(&self.new_trace(tag)),
+        }
+    }
 
-        TraceTags { traces }
+    /// Applies all the settings of the given tag, or of the tags matching the given pattern.
+    ///
+    /// If the tag doesn't exist, a new trace is created with these settings.
+    pub fn set_config(&mut self, tag: &str, config: TraceConfig) {
+        self.configure(tag, Rule::Config(config));
+    }
+
+    /// Saves the settings of all the tags, including the ones applied with patterns.
+    ///
+    /// The recorded events are not saved. See [TraceTags::restore_config].
+    pub fn save_config(&self) -> SavedConfig {
+        SavedConfig {
+            configs: self
+                .traces
+                .iter()
+                .map(|(tag, trace)| (tag.to_string(), TraceConfig::of(trace)))
+                .collect(),
+            rules: self.rules.clone(),
+        }
+    }
+
+    /// Restores the settings saved with [TraceTags::save_config].
+    ///
+    /// The tags created since then get the settings they would have been created with at the
+    /// time, and their recorded events are kept.
+    pub fn restore_config(&mut self, saved: SavedConfig) {
+        self.rules = saved.rules;
+
+        let tags: Vec<_> = self.traces.keys().cloned().collect();
+        for tag in tags {
+            let config = match saved.configs.get(tag.as_ref()) {
+                Some(config) => config.clone(),
+                None => TraceConfig::of(&self.new_trace(&tag)),
+            };
+            if let Some(trace) = self.traces.get_mut(&tag) {
+                config.apply(trace);
+            }
+        }
+    }
+
+    /// Applies a setting to the given tag, or to all the tags matching the given pattern.
+    fn configure(&mut self, tag: &str, rule: Rule) {
+        if !is_pattern(tag) {
+            rule.apply(self.trace_mut(tag.to_string()));
+            return;
+        }
+
+        for (name, trace) in self.traces.iter_mut() {
+            if matches_tag(tag, name) {
+                rule.apply(trace);
+            }
+        }
+
+        // A later setting of the same kind with the same pattern replaces the earlier one.
+        self.rules.retain(|(pattern, previous)| {
+            pattern != tag || std::mem::discriminant(previous) != std::mem::discriminant(&rule)
+        });
+        self.rules.push((tag.to_string(), rule));
     }
 
     /// Resets the trace associated with the given tag.
     ///
     /// If the tag doesn't exist, a new trace is created and then reset.
-    pub fn clear(&mut self, tag: &'static str) {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
+    pub fn clear(&mut self, tag: &str) {
+        let t = self.trace_mut(tag.to_string());
         t.clear();
     }
 
     /// Retrieves the trace associated with the given tag as a string.
     ///
     /// Returns `None` if the tag doesn't exist.
-    pub fn get_trace(&self, tag: &'static str) -> Option<String> {
+    pub fn get_trace(&self, tag: &str) -> Option<String> {
         self.traces.get(tag).map(|t| t.to_string())
     }
 
-    /// Activates the trace associated with the given tag.
+    /// Retrieves the trace associated with the given tag, rendered for snapshot tests.
+    ///
+    /// Returns `None` if the tag doesn't exist. See [snapshot::render](crate::snapshot::render).
+    pub fn get_snapshot(&self, tag: &str) -> Option<String> {
+        self.traces.get(tag).map(crate::snapshot::render)
+    }
+
+    /// Retrieves the trace associated with the given tag, rendered with the given view.
+    ///
+    /// Returns `None` if the tag doesn't exist. See [view::render](crate::view::render).
+    pub fn get_trace_view(&self, tag: &str, view: TraceView) -> Option<String> {
+        self.traces.get(tag).map(|t| crate::view::render(t, view))
+    }
+
+    /// Finds the furthest failure in the trace associated with the given tag.
+    ///
+    /// Returns `None` if the tag doesn't exist or if no leaf parser failed.
+    /// See [FurthestFailure::from_trace].
+    pub fn furthest_failure(&self, tag: &str, source: &str) -> Option<FurthestFailure> {
+        self.traces
+            .get(tag)
+            .and_then(|t| FurthestFailure::from_trace(t, source))
+    }
+
+    /// Activates the trace associated with the given tag, or the traces matching the given
+    /// pattern.
     ///
     /// If the tag doesn't exist, a new trace is created and activated.
-    pub fn activate(&mut self, tag: &'static str) {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
-        t.active = true;
+    pub fn activate(&mut self, tag: &str) {
+        self.configure(tag, Rule::Active(true));
     }
 
-    /// Deactivates the trace associated with the given tag.
+    /// Deactivates the trace associated with the given tag, or the traces matching the given
+    /// pattern.
     ///
     /// If the tag doesn't exist, a new trace is created (but remains inactive).
-    pub fn deactivate(&mut self, tag: &'static str) {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
-        t.active = false;
+    pub fn deactivate(&mut self, tag: &str) {
+        self.configure(tag, Rule::Active(false));
     }
 
-    /// Activates real-time printing for the trace associated with the given tag.
+    /// Activates real-time printing for the trace associated with the given tag, or the traces
+    /// matching the given pattern.
     ///
     /// This method is only available when the `trace-print` feature is enabled.
     #[cfg(feature = "trace-print")]
-    pub fn activate_trace_print(&mut self, tag: &'static str) {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
-        t.print = true;
+    pub fn activate_trace_print(&mut self, tag: &str) {
+        self.configure(tag, Rule::Print(true));
     }
 
-    /// Deactivates real-time printing for the trace associated with the given tag.
+    /// Deactivates real-time printing for the trace associated with the given tag, or the
+    /// traces matching the given pattern.
     ///
     /// This method is only available when the `trace-print` feature is enabled.
     #[cfg(feature = "trace-print")]
-    pub fn deactivate_trace_print(&mut self, tag: &'static str) {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
-        t.print = false;
+    pub fn deactivate_trace_print(&mut self, tag: &str) {
+        self.configure(tag, Rule::Print(false));
+    }
+
+    /// Switches the trace associated with the given tag, or the traces matching the given
+    /// pattern, to statistics-only mode.
+    ///
+    /// In this mode, per-location counters are kept instead of the events.
+    /// If the tag doesn't exist, a new trace is created in statistics-only mode.
+    pub fn activate_trace_stats(&mut self, tag: &str) {
+        self.configure(tag, Rule::Capture(TraceCapture::Stats));
     }
 
-    /// Sets the maximum nesting level for the trace associated with the given tag.
+    /// Switches the trace associated with the given tag, or the traces matching the given
+    /// pattern, back to recording events.
+    ///
+    /// The statistics recorded so far are dropped.
+    pub fn deactivate_trace_stats(&mut self, tag: &str) {
+        self.configure(tag, Rule::Capture(TraceCapture::Events));
+    }
+
+    /// Retrieves the statistics of the trace associated with the given tag.
+    ///
+    /// Returns `None` if the tag doesn't exist or isn't in statistics-only mode.
+    pub fn get_stats(&self, tag: &str) -> Option<TraceStats> {
+        self.traces.get(tag).and_then(|t| t.stats.clone())
+    }
+
+    /// Sets the maximum nesting level for the trace associated with the given tag, or the
+    /// traces matching the given pattern.
     ///
     /// When the nesting level exceeds this value, the parser will panic.
     /// This method is only available when the `trace-max-level` feature is enabled.
     #[cfg(feature = "trace-max-level")]
-    pub fn panic_on_level(&mut self, tag: &'static str, level: Option<usize>) {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
-        t.panic_on_level = level;
+    pub fn panic_on_level(&mut self, tag: &str, level: Option<usize>) {
+        self.configure(tag, Rule::MaxLevel(level));
+    }
+
+    /// Sets which events the trace associated with the given tag, or the traces matching the
+    /// given pattern, keep.
+    ///
+    /// The events that aren't kept anymore are dropped. If the tag doesn't exist, a new trace
+    /// is created with this retention.
+    pub fn set_retention(&mut self, tag: &str, retention: TraceRetention) {
+        self.configure(tag, Rule::Retention(retention));
+    }
+
+    /// Sets the filters deciding which parsers are recorded in the trace associated with the
+    /// given tag, or the traces matching the given pattern.
+    ///
+    /// If the tag doesn't exist, a new trace is created with these filters.
+    /// See [filters](crate::filters).
+    pub fn set_filters(&mut self, tag: &str, filters: TraceFilters) {
+        self.configure(tag, Rule::Filters(filters));
+    }
+
+    /// Sets the deepest nesting level recorded in the trace associated with the given tag, or
+    /// the traces matching the given pattern. With `None`, every level is recorded.
+    ///
+    /// The parsers nested deeper are summarized in the parser at this level. If the tag doesn't
+    /// exist, a new trace is created with this depth. See [Trace::record_depth].
+    pub fn set_record_depth(&mut self, tag: &str, depth: Option<usize>) {
+        self.configure(tag, Rule::RecordDepth(depth));
+    }
+
+    /// Sets how the parsers silenced with [silence_tree!](crate::silence_tree!) show in the trace
+    /// associated with the given tag, or the traces matching the given pattern.
+    ///
+    /// If the tag doesn't exist, a new trace is created with this mode.
+    /// This method is only available when the `trace-silencing` feature is enabled.
+    #[cfg(feature = "trace-silencing")]
+    pub fn set_silence(&mut self, tag: &str, silence: TraceSilence) {
+        self.configure(tag, Rule::Silence(silence));
     }
 
     /// Records the opening of a parser in the trace associated with the given tag.
     pub fn open<I>(
         &mut self,
-        tag: &'static str,
+        tag: impl Into<Cow<'static, str>>,
         context: Option<&'static str>,
         input: I,
         location: &'static str,
@@ -98,14 +439,14 @@ impl TraceTags {
     ) where
         I: AsRef<str>,
     {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
+        let t = self.trace_mut(tag);
         t.open(context, input, location, silent);
     }
 
     /// Records the closing of a parser in the trace associated with the given tag.
     pub fn close<I, O: Debug, E: Debug>(
         &mut self,
-        tag: &'static str,
+        tag: impl Into<Cow<'static, str>>,
         context: Option<&'static str>,
         input: I,
         location: &'static str,
@@ -114,14 +455,52 @@ impl TraceTags {
     ) where
         I: AsRef<str>,
     {
-        let t = self.traces.entry(tag).or_insert(Trace::default());
+        let tag = tag.into();
+        let collect = self.collect;
+        let t = self.entry(&tag);
         t.close(context, input, location, result, silent);
+        if collect {
+            crate::collector::forward(&tag, t);
+        }
+    }
+
+    /// Records a memoization lookup in the trace associated with the given tag.
+    #[cfg(feature = "trace-memo")]
+    pub fn memo<I, O: Debug, E: Debug>(
+        &mut self,
+        tag: impl Into<Cow<'static, str>>,
+        context: Option<&'static str>,
+        input: I,
+        location: &'static str,
+        cached: Option<&IResult<I, O, E>>,
+        silent: bool,
+    ) where
+        I: AsRef<str>,
+    {
+        let t = self.trace_mut(tag);
+        t.memo(context, input, location, cached, silent);
+    }
+
+    /// Records a silenced parser in the trace associated with the given tag, from the events
+    /// it recorded in the silent trace.
+    #[cfg(feature = "trace-silencing")]
+    #[allow(clippy::ptr_arg)]
+    pub(crate) fn silenced(&mut self, tag: &Cow<'static, str>, events: &[TraceEvent]) {
+        let collect = self.collect;
+        let t = self.entry(tag);
+        #[cfg(feature = "trace-print")]
+        t.silenced(events, false);
+        #[cfg(not(feature = "trace-print"))]
+        t.silenced(events);
+        if collect {
+            crate::collector::forward(tag, t);
+        }
     }
 
     /// Returns the current nesting level for the trace associated with the given tag.
     ///
     /// If the tag doesn't exist, returns 0.
-    pub fn level_for_tag(&self, tag: &'static str) -> usize {
+    pub fn level_for_tag(&self, tag: &str) -> usize {
         self.traces.get(tag).map(|t| t.level).unwrap_or(0)
     }
 }
@@ -226,6 +605,193 @@ mod tests {
         assert!(trace.contains("-> Ok"));
     }
 
+    #[test]
+    fn test_activate_deactivate_trace_stats() {
+        let mut trace_tags = TraceTags::new();
+        assert!(trace_tags.get_stats(DEFAULT_TAG).is_none());
+
+        trace_tags.activate_trace_stats(DEFAULT_TAG);
+        trace_tags.open(DEFAULT_TAG, None, "input", "location", false);
+        trace_tags.close::<_, _, nom::error::VerboseError<&str>>(
+            DEFAULT_TAG,
+            None,
+            "input",
+            "location",
+            &Ok(("", "input")),
+            false,
+        );
+
+        assert!(trace_tags.traces[DEFAULT_TAG].events.is_empty());
+        let stats = trace_tags.get_stats(DEFAULT_TAG).unwrap();
+        assert_eq!(stats.locations["location"].calls, 1);
+        assert_eq!(stats.locations["location"].consumed, 5);
+
+        trace_tags.deactivate_trace_stats(DEFAULT_TAG);
+        assert!(trace_tags.get_stats(DEFAULT_TAG).is_none());
+    }
+
+    #[test]
+    fn test_runtime_tags() {
+        let mut trace_tags = TraceTags::new();
+        for file in ["a.json", "b.json"] {
+            let tag = format!("file:{}", file);
+            trace_tags.open(tag.clone(), None, "input", "location", false);
+        }
+
+        let mut tags: Vec<_> = trace_tags.tags().collect();
+        tags.sort();
+        assert_eq!(tags, vec![DEFAULT_TAG, "file:a.json", "file:b.json"]);
+
+        let removed = trace_tags.remove("file:a.json").unwrap();
+        assert_eq!(removed.events.len(), 1);
+        assert!(trace_tags.remove("file:a.json").is_none());
+
+        let drained: Vec<_> = trace_tags.drain().collect();
+        assert_eq!(drained.len(), 2);
+        assert!(trace_tags.traces.is_empty());
+    }
+
+    #[test]
+    fn test_static_tags_stay_borrowed() {
+        let mut trace_tags = TraceTags::new();
+        trace_tags.open("json", None, "input", "location", false);
+        trace_tags.open(
+            String::from("file:a.json"),
+            None,
+            "input",
+            "location",
+            false,
+        );
+
+        let (json, _) = trace_tags.traces.get_key_value("json").unwrap();
+        assert!(matches!(json, Cow::Borrowed("json")));
+        let (file, _) = trace_tags.traces.get_key_value("file:a.json").unwrap();
+        assert!(matches!(file, Cow::Owned(_)));
+    }
+
+    #[cfg(all(
+        feature = "trace-env",
+        feature = "trace-print",
+        feature = "trace-max-level"
+    ))]
+    #[test]
+    fn test_apply_spec() {
+        let mut trace_tags = TraceTags::new();
+        trace_tags
+            .apply_spec("-*,json.*:print,http:max=64:format=stats")
+            .unwrap();
+
+        assert!(!trace_tags.traces[DEFAULT_TAG].active);
+        let json = trace_tags.trace_mut("json.string");
+        assert!(json.active && json.print);
+        let http = trace_tags.trace_mut("http");
+        assert!(http.active && !http.print);
+        assert_eq!(http.panic_on_level, Some(64));
+        assert!(http.stats.is_some());
+
+        assert!(trace_tags.apply_spec("default,json:oops").is_err());
+        assert!(!trace_tags.traces[DEFAULT_TAG].active);
+
+        trace_tags.apply_spec("http:format=tree").unwrap();
+        assert!(trace_tags.traces["http"].stats.is_none());
+    }
+
+    #[test]
+    fn test_config() {
+        let mut trace_tags = TraceTags::new();
+        let saved = trace_tags.save_config();
+
+        trace_tags.set_config("json.*", TraceConfig::new().active(false));
+        assert!(!trace_tags.traces.contains_key("json.string"));
+        assert!(!trace_tags.config("json.string").active);
+        assert!(trace_tags.config(DEFAULT_TAG).active);
+
+        let config = TraceConfig::new().capture(TraceCapture::Stats);
+        trace_tags.set_config(DEFAULT_TAG, config.clone());
+        assert_eq!(trace_tags.config(DEFAULT_TAG), config);
+        trace_tags.open("json.string", None, "input", "location", false);
+        assert!(trace_tags.traces["json.string"].events.is_empty());
+
+        trace_tags.restore_config(saved);
+        assert_eq!(trace_tags.config(DEFAULT_TAG), TraceConfig::default());
+        assert_eq!(trace_tags.config("json.string"), TraceConfig::default());
+        assert_eq!(trace_tags.config("json.number"), TraceConfig::default());
+    }
+
+    #[test]
+    fn test_filters() {
+        use crate::filters::TraceFilter;
+
+        let mut trace_tags = TraceTags::new();
+        trace_tags.set_filters("*", TraceFilters::new().exclude(TraceFilter::name("ws")));
+
+        trace_tags.open("json", None, "a b", "value", false);
+        trace_tags.open("json", None, " b", "ws", false);
+        trace_tags.open("json", None, " b", "multispace0", false);
+        for location in ["multispace0", "ws"] {
+            trace_tags.close::<_, _, nom::error::VerboseError<&str>>(
+                "json",
+                None,
+                "b",
+                location,
+                &Ok(("b", " ")),
+                false,
+            );
+        }
+
+        let events = &trace_tags.traces["json"].events;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].location, "value");
+        assert_eq!(trace_tags.traces["json"].level, 1);
+    }
+
+    #[test]
+    fn test_matches_tag() {
+        assert!(matches_tag("json", "json"));
+        assert!(!matches_tag("json", "json.string"));
+        assert!(matches_tag("json.*", "json"));
+        assert!(matches_tag("json.*", "json.string"));
+        assert!(matches_tag("json.*", "json.number.float"));
+        assert!(!matches_tag("json.*", "jsonl"));
+        assert!(!matches_tag("json.*", "http.header"));
+        assert!(matches_tag("*.string", "json.string"));
+        assert!(!matches_tag("*.string", "json.string.escape"));
+        assert!(matches_tag("*", DEFAULT_TAG));
+
+        assert!(is_pattern("json.*"));
+        assert!(!is_pattern("json.string"));
+    }
+
+    #[test]
+    fn test_pattern_settings() {
+        let mut trace_tags = TraceTags::new();
+        trace_tags.open("json.string", None, "input", "location", false);
+
+        trace_tags.deactivate("json.*");
+        assert!(!trace_tags.traces["json.string"].active);
+        assert!(trace_tags.traces[DEFAULT_TAG].active);
+        assert!(!trace_tags.traces.contains_key("json.*"));
+
+        // Tags created afterward get the settings of the patterns they match, in order.
+        trace_tags.activate("json.number.*");
+        trace_tags.open("json.number.float", None, "input", "location", false);
+        trace_tags.open("json.null", None, "input", "location", false);
+        trace_tags.open("http.header", None, "input", "location", false);
+        assert!(trace_tags.traces["json.number.float"].active);
+        assert!(!trace_tags.traces["json.null"].active);
+        assert!(trace_tags.traces["http.header"].active);
+        assert_eq!(trace_tags.traces["json.number.float"].events.len(), 1);
+        assert!(trace_tags.traces["json.null"].events.is_empty());
+
+        trace_tags.activate("json.*");
+        trace_tags.open("json.bool", None, "input", "location", false);
+        assert!(trace_tags.traces["json.string"].active);
+        assert!(trace_tags.traces["json.bool"].active);
+
+        trace_tags.activate_trace_stats("http.*");
+        assert!(trace_tags.get_stats("http.header").is_some());
+    }
+
     #[cfg(feature = "trace-print")]
     mod print_tests {
         use super::*;
@@ -246,6 +812,10 @@ mod tests {
             assert!(trace_tags.traces[custom_tag].print);
             trace_tags.deactivate_trace_print(custom_tag);
             assert!(!trace_tags.traces[custom_tag].print);
+
+            trace_tags.activate_trace_print("json.*");
+            assert!(!trace_tags.traces[custom_tag].print);
+            assert!(trace_tags.trace_mut("json.string").print);
         }
     }
 
@@ -261,6 +831,10 @@ mod tests {
 
             trace_tags.panic_on_level(DEFAULT_TAG, None);
             assert_eq!(trace_tags.traces[DEFAULT_TAG].panic_on_level, None);
+
+            trace_tags.panic_on_level("json.*", Some(64));
+            assert_eq!(trace_tags.traces[DEFAULT_TAG].panic_on_level, None);
+            assert_eq!(trace_tags.trace_mut("json.string").panic_on_level, Some(64));
         }
 
         #[test]
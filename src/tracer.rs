@@ -0,0 +1,212 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(feature = "trace-silencing")]
+use crate::silence_tree;
+use {
+    crate::{context::TraceContext, tags::TraceTags, tr, TraceError, DEFAULT_TAG},
+    nom::{IResult, Parser},
+    std::{borrow::Cow, fmt::Debug},
+};
+
+/// A tracer owning its own traces.
+///
+/// Parsers wrapped by a tracer record into its [TraceTags], never into the thread-local
+/// storage. This lets a library trace its parsers without interfering with an application
+/// that also uses nom-tracer, and keeps two independent parses on the same thread apart.
+///
+/// The parsers nested in a wrapped parser, including the ones using the tracing macros,
+/// record into the tracer too. Its traces are never forwarded to an installed
+/// [TraceCollector](crate::collector::TraceCollector).
+///
+/// A tracer only isolates traces. While [global coverage](crate::coverage) is collected, it
+/// counts the parsers run by every tracer, as it does for every thread.
+///
+/// ```
+/// use nom_tracer::{tracer::Tracer, DEFAULT_TAG};
+/// use nom::{bytes::complete::tag, IResult};
+///
+/// let tracer = Tracer::new();
+/// let mut hello = tracer.wrap("hello", tag::<_, _, nom::error::Error<_>>("hello"));
+///
+/// hello("hello world").unwrap();
+/// assert!(tracer.get_trace(DEFAULT_TAG).unwrap().contains("hello"));
+/// ```
+#[derive(Clone, Default)]
+pub struct Tracer {
+    context: TraceContext,
+}
+
+impl Tracer {
+    /// Creates a tracer with a fresh [TraceTags], as created by [TraceTags::new].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a tracer recording into the given [TraceTags].
+    pub fn with_tags(tags: TraceTags) -> Self {
+        Self {
+            context: TraceContext::with_tags(tags),
+        }
+    }
+
+    /// Returns the context the tracer records into, for example to
+    /// [instrument](TraceContext::instrument) a future.
+    pub fn context(&self) -> &TraceContext {
+        &self.context
+    }
+
+    /// Wraps a parser to trace it with the default tag and no context.
+    pub fn wrap<I, O, E, F>(
+        &self,
+        name: &'static str,
+        parser: F,
+    ) -> impl FnMut(I) -> IResult<I, O, E>
+    where
+        I: AsRef<str>,
+        F: Parser<I, O, E>,
+        I: Clone,
+        O: Debug,
+        E: TraceError<I>,
+    {
+        self.wrap_with(DEFAULT_TAG, None, name, parser)
+    }
+
+    /// Wraps a parser to trace it, like [tr] does with the thread-local storage.
+    pub fn wrap_with<I, O, E, F>(
+        &self,
+        tag: impl Into<Cow<'static, str>>,
+        context: Option<&'static str>,
+        name: &'static str,
+        parser: F,
+    ) -> impl FnMut(I) -> IResult<I, O, E>
+    where
+        I: AsRef<str>,
+        F: Parser<I, O, E>,
+        I: Clone,
+        O: Debug,
+        E: TraceError<I>,
+    {
+        let tracer = self.context.clone();
+        let mut parser = tr(tag, context, name, parser);
+
+        move |input: I| tracer.enter(|| parser(input))
+    }
+
+    /// Wraps a parser to silence its subtree, like [silence_tree()] does with the thread-local
+    /// storage.
+    #[cfg(feature = "trace-silencing")]
+    pub fn silence<I, O, E, F>(
+        &self,
+        tag: impl Into<Cow<'static, str>>,
+        context: Option<&'static str>,
+        name: &'static str,
+        parser: F,
+    ) -> impl FnMut(I) -> IResult<I, O, E>
+    where
+        I: AsRef<str>,
+        F: Parser<I, O, E>,
+        I: Clone,
+        O: Debug,
+        E: TraceError<I>,
+    {
+        let tracer = self.context.clone();
+        let mut parser = silence_tree(tag, context, name, parser);
+
+        move |input: I| tracer.enter(|| parser(input))
+    }
+
+    /// Retrieves the trace associated with the given tag as a string.
+    ///
+    /// Returns `None` if the tag doesn't exist.
+    pub fn get_trace(&self, tag: &str) -> Option<String> {
+        self.context.with(|tags| tags.get_trace(tag))
+    }
+
+    /// Runs a function with the traces recorded so far by this tracer.
+    pub fn with<R>(&self, f: impl FnOnce(&TraceTags) -> R) -> R {
+        self.context.with(f)
+    }
+
+    /// Runs a function with mutable access to the traces of this tracer, for example to
+    /// activate or reset some tags.
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut TraceTags) -> R) -> R {
+        self.context.with_mut(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{get_trace, reset_trace, trace},
+        nom::{bytes::complete::tag, error::VerboseError, sequence::pair},
+    };
+
+    #[test]
+    fn test_tracer_owns_its_traces() {
+        reset_trace!();
+
+        let first = Tracer::new();
+        let second = Tracer::new();
+        let mut hello = first.wrap("hello", tag::<_, _, VerboseError<_>>("hello"));
+        let mut world = second.wrap_with(
+            "custom",
+            Some("world"),
+            "world",
+            tag::<_, _, VerboseError<_>>("world"),
+        );
+
+        assert!(hello("hello").is_ok());
+        assert!(world("world").is_ok());
+        assert!(hello("world").is_err());
+
+        assert_eq!(get_trace!().unwrap(), "");
+        first.with(|tags| assert_eq!(tags.traces[DEFAULT_TAG].events.len(), 4));
+        assert!(second.get_trace(DEFAULT_TAG).unwrap().is_empty());
+        assert!(second.get_trace("custom").unwrap().contains("world"));
+
+        first.with_mut(|tags| tags.clear(DEFAULT_TAG));
+        assert!(first.get_trace(DEFAULT_TAG).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tracer_records_nested_macros() {
+        fn inner(input: &str) -> nom::IResult<&str, &str> {
+            trace!(tag("a"))(input)
+        }
+
+        reset_trace!();
+        let tracer = Tracer::new();
+        let mut parser = tracer.wrap("outer", pair(inner, inner));
+        assert!(parser("aa").is_ok());
+
+        assert_eq!(get_trace!().unwrap(), "");
+        tracer.with(|tags| {
+            let events = &tags.traces[DEFAULT_TAG].events;
+            assert_eq!(events.len(), 6);
+            assert!(events[1].location.ends_with("inner"));
+            assert_eq!(events[1].level, 1);
+        });
+    }
+
+    #[cfg(feature = "trace-silencing")]
+    #[test]
+    fn test_tracer_silence() {
+        let tracer = Tracer::new();
+        let mut parser = tracer.silence(DEFAULT_TAG, None, "silenced", |i| {
+            crate::tr(
+                DEFAULT_TAG,
+                None,
+                "hidden",
+                tag::<_, _, VerboseError<_>>("a"),
+            )(i)
+        });
+        assert!(parser("a").is_ok());
+
+        let trace = tracer.get_trace(DEFAULT_TAG).unwrap();
+        assert_eq!(trace.lines().count(), 1);
+        assert!(trace.contains("silenced, 1 nested invocations hidden"));
+        assert!(!trace.contains("hidden("));
+    }
+}
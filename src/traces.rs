@@ -1,12 +1,22 @@
 // Copyright (c) Hexbee
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "trace-silencing")]
+use crate::config::TraceSilence;
 #[cfg(feature = "trace-print")]
-use crate::print;
+use crate::config::TraceSink;
 use {
-    crate::events::{TraceEvent, TraceEventType},
+    crate::{
+        config::TraceRetention,
+        events::{TraceEvent, TraceEventType, TraceSummary},
+        filters::{FilterState, TraceFilters},
+        stats::TraceStats,
+    },
     nom::IResult,
-    std::fmt::{Debug, Display, Formatter},
+    std::{
+        collections::VecDeque,
+        fmt::{Debug, Display, Formatter},
+    },
 };
 
 /// Represents a single trace in the parsing process.
@@ -15,7 +25,7 @@ use {
 /// and can be configured for various tracing behaviors.
 pub struct Trace {
     /// The collection of trace events.
-    pub events: Vec<TraceEvent>,
+    pub events: VecDeque<TraceEvent>,
     /// The current nesting level of the trace.
     pub level: usize,
     /// Whether the trace is currently active and recording events.
@@ -23,29 +33,84 @@ pub struct Trace {
     /// Whether to print trace events in real-time.
     #[cfg(feature = "trace-print")]
     pub print: bool,
+    /// Where to print trace events in real-time.
+    #[cfg(feature = "trace-print")]
+    pub sink: TraceSink,
     /// The maximum nesting level before panicking, if set.
     #[cfg(feature = "trace-max-level")]
     pub panic_on_level: Option<usize>,
+    /// The statistics recorded instead of the events, if the trace is in statistics-only mode.
+    pub stats: Option<TraceStats>,
+    /// Which events the trace keeps.
+    pub retention: TraceRetention,
+    /// The number of events dropped because of the retention.
+    pub dropped: usize,
+    /// The filters deciding which parsers are recorded.
+    pub filters: TraceFilters,
+    /// The deepest nesting level recorded, if set.
+    ///
+    /// The parsers nested deeper aren't recorded individually: the parser at this level gets a
+    /// [Summary](TraceEventType::Summary) of them instead, before its close event.
+    pub record_depth: Option<usize>,
+    /// How the parsers silenced with [silence_tree!](crate::silence_tree!) show in the trace.
+    #[cfg(feature = "trace-silencing")]
+    pub silence: TraceSilence,
+    /// The summary of the parsers nested below the recording depth, while they are running.
+    pub(crate) summary: Option<TraceSummary>,
+    /// The state of the filters while parsers are running.
+    pub(crate) filter_state: FilterState,
+    /// The index of the first event of the top-level invocations whose result isn't known yet,
+    /// with the [FailuresOnly](TraceRetention::FailuresOnly) retention.
+    pub(crate) unresolved: usize,
+    /// The positions of the retained top-level invocations, counting the events removed from
+    /// the front of the trace, with the [LastInvocations](TraceRetention::LastInvocations)
+    /// retention.
+    pub(crate) invocations: VecDeque<usize>,
+    /// The number of events removed from the front of the trace.
+    pub(crate) removed: usize,
 }
 
 impl Default for Trace {
     fn default() -> Self {
         Self {
-            events: Vec::new(),
+            events: VecDeque::new(),
             level: 0,
             active: true,
             #[cfg(feature = "trace-print")]
             print: false,
+            #[cfg(feature = "trace-print")]
+            sink: TraceSink::default(),
             #[cfg(feature = "trace-max-level")]
             panic_on_level: None,
+            stats: None,
+            retention: TraceRetention::All,
+            dropped: 0,
+            filters: TraceFilters::default(),
+            record_depth: None,
+            #[cfg(feature = "trace-silencing")]
+            silence: TraceSilence::default(),
+            summary: None,
+            filter_state: FilterState::default(),
+            unresolved: 0,
+            invocations: VecDeque::new(),
+            removed: 0,
         }
     }
 }
 
 impl Trace {
-    /// Clears all recorded events and resets the nesting level to 0.
+    /// Clears all recorded events and statistics, and resets the nesting level to 0.
     pub fn clear(&mut self) {
         self.events.clear();
+        self.invocations.clear();
+        self.removed = 0;
+        self.dropped = 0;
+        self.filter_state = FilterState::default();
+        self.summary = None;
+        self.unresolved = 0;
+        if let Some(stats) = self.stats.as_mut() {
+            stats.clear();
+        }
         self.level = 0;
     }
 
@@ -77,6 +142,28 @@ impl Trace {
                 }
             }
 
+            if !self
+                .filter_state
+                .open(&self.filters, self.level, location, context)
+            {
+                self.level += 1;
+                return self.level;
+            }
+
+            if let Some(stats) = self.stats.as_mut() {
+                stats.open(location, self.level);
+                self.level += 1;
+                return self.level;
+            }
+
+            if self.record_depth.is_some_and(|depth| self.level > depth) {
+                self.summary
+                    .get_or_insert_with(TraceSummary::default)
+                    .open(self.level);
+                self.level += 1;
+                return self.level;
+            }
+
             let event = TraceEvent {
                 level: self.level,
                 location,
@@ -86,11 +173,9 @@ impl Trace {
             };
 
             #[cfg(feature = "trace-print")]
-            if self.print && !silent {
-                print(format!("{}", event));
-            }
-
-            self.events.push(event);
+            self.push(event, silent);
+            #[cfg(not(feature = "trace-print"))]
+            self.push(event);
             self.level += 1;
         }
 
@@ -125,32 +210,344 @@ impl Trace {
             }
             self.level -= 1;
 
-            let event_type = match result {
-                Ok((_, o)) => TraceEventType::CloseOk(format!("{:?}", o)),
-                Err(nom::Err::Error(e)) => TraceEventType::CloseError(format!("{:?}", e)),
-                Err(nom::Err::Failure(e)) => TraceEventType::CloseFailure(format!("{:?}", e)),
-                Err(nom::Err::Incomplete(i)) => TraceEventType::CloseIncomplete(*i),
-            };
+            if !self.filter_state.close(&self.filters, self.level) {
+                return self.level;
+            }
+
+            if let Some(stats) = self.stats.as_mut() {
+                stats.close(location, &input, result);
+                return self.level;
+            }
+
+            if self.record_depth.is_some_and(|depth| self.level > depth) {
+                if let Some(summary) = self.summary.as_mut() {
+                    summary.close(result);
+                }
+                return self.level;
+            }
+            if let Some(summary) = self.summary.take() {
+                let event = TraceEvent {
+                    level: self.level + 1,
+                    location,
+                    context: None,
+                    input: String::from(input.as_ref()),
+                    event: TraceEventType::Summary(summary),
+                };
+
+                #[cfg(feature = "trace-print")]
+                self.push(event, silent);
+                #[cfg(not(feature = "trace-print"))]
+                self.push(event);
+            }
 
             let event = TraceEvent {
                 level: self.level,
                 location,
                 context,
                 input: String::from(input.as_ref()),
-                event: event_type,
+                event: close_event_type(result),
             };
 
             #[cfg(feature = "trace-print")]
-            if self.print && !silent {
-                print(format!("{}", event));
+            self.push(event, silent);
+            #[cfg(not(feature = "trace-print"))]
+            self.push(event);
+        }
+
+        self.level
+    }
+
+    /// Records a memoization lookup for a parser in the trace.
+    ///
+    /// A lookup doesn't change the nesting level: on a miss, the parser runs and records
+    /// its own events, while on a hit the cached result is recorded in place of the parser.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - Optional context information for the event.
+    /// * `input` - The input being parsed.
+    /// * `location` - The location (usually function name) where this event occurred.
+    /// * `cached` - The cached result on a hit, or `None` on a miss.
+    /// * `silent` - Whether to suppress real-time printing of this event.
+    ///
+    /// # Returns
+    ///
+    /// The current nesting level.
+    #[cfg(feature = "trace-memo")]
+    pub fn memo<I: AsRef<str>, O: Debug, E: Debug>(
+        &mut self,
+        context: Option<&'static str>,
+        input: I,
+        location: &'static str,
+        cached: Option<&IResult<I, O, E>>,
+        #[cfg(feature = "trace-print")] silent: bool,
+        #[cfg(not(feature = "trace-print"))] _silent: bool,
+    ) -> usize {
+        if self.active
+            && self.stats.is_none()
+            && self.filter_state.event(&self.filters, location, context)
+        {
+            // Below the recording depth, a hit is counted as an invocation, and a miss is
+            // followed by the invocation of the parser.
+            if self.record_depth.is_some_and(|depth| self.level > depth) {
+                if let Some(result) = cached {
+                    let summary = self.summary.get_or_insert_with(TraceSummary::default);
+                    summary.open(self.level);
+                    summary.close(result);
+                }
+                return self.level;
             }
 
-            self.events.push(event);
+            let event = TraceEvent {
+                level: self.level,
+                location,
+                context,
+                input: String::from(input.as_ref()),
+                event: match cached {
+                    Some(result) => TraceEventType::MemoHit(Box::new(close_event_type(result))),
+                    None => TraceEventType::MemoMiss,
+                },
+            };
+
+            #[cfg(feature = "trace-print")]
+            self.push(event, silent);
+            #[cfg(not(feature = "trace-print"))]
+            self.push(event);
         }
 
         self.level
     }
 
+    /// Records a parser silenced with [silence_tree!](crate::silence_tree!), from the events it
+    /// recorded in the silent trace: its open event first, and its close event last.
+    ///
+    /// With the [Summary](TraceSilence::Summary) mode, a single
+    /// [Silenced](TraceEventType::Silenced) event is recorded at the current nesting level. With
+    /// the [ExpandOnFailure](TraceSilence::ExpandOnFailure) mode, the events are recorded instead
+    /// if the silenced parser didn't return `Ok`.
+    #[cfg(feature = "trace-silencing")]
+    pub(crate) fn silenced(
+        &mut self,
+        events: &[TraceEvent],
+        #[cfg(feature = "trace-print")] silent: bool,
+    ) {
+        let [open, nested @ .., close] = events else {
+            return;
+        };
+        if !self.active
+            || self.stats.is_some()
+            || self.silence == TraceSilence::Hide
+            || !self
+                .filter_state
+                .event(&self.filters, open.location, open.context)
+        {
+            return;
+        }
+
+        if self.silence == TraceSilence::ExpandOnFailure
+            && !matches!(close.event, TraceEventType::CloseOk(_))
+        {
+            #[cfg(feature = "trace-print")]
+            self.expand(events, silent);
+            #[cfg(not(feature = "trace-print"))]
+            self.expand(events);
+            return;
+        }
+
+        // Below the recording depth, the silenced parser is counted as a single invocation.
+        if self.record_depth.is_some_and(|depth| self.level > depth) {
+            let summary = self.summary.get_or_insert_with(TraceSummary::default);
+            summary.open(self.level);
+            summary.record(&close.event);
+            return;
+        }
+
+        let hidden = nested
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event.event,
+                    TraceEventType::Open | TraceEventType::MemoHit(_)
+                )
+            })
+            .count();
+        let event = TraceEvent {
+            level: self.level,
+            location: open.location,
+            context: open.context,
+            input: open.input.clone(),
+            event: TraceEventType::Silenced {
+                result: Box::new(close.event.clone()),
+                hidden,
+            },
+        };
+
+        #[cfg(feature = "trace-print")]
+        self.push(event, silent);
+        #[cfg(not(feature = "trace-print"))]
+        self.push(event);
+    }
+
+    /// Records the events of a silenced parser as if it wasn't silenced, moved to the current
+    /// nesting level.
+    #[cfg(feature = "trace-silencing")]
+    fn expand(&mut self, events: &[TraceEvent], #[cfg(feature = "trace-print")] silent: bool) {
+        let Some(first) = events.first() else {
+            return;
+        };
+        let base = first.level;
+
+        for event in events {
+            let event = TraceEvent {
+                level: event.level - base + self.level,
+                ..event.clone()
+            };
+
+            // Below the recording depth, the events are summarized like the ones of the parsers
+            // that weren't silenced.
+            if self.record_depth.is_some_and(|depth| event.level > depth) {
+                let summary = self.summary.get_or_insert_with(TraceSummary::default);
+                match &event.event {
+                    TraceEventType::Open => summary.open(event.level),
+                    TraceEventType::MemoHit(result) => {
+                        summary.open(event.level);
+                        summary.record(result);
+                    }
+                    result => summary.record(result),
+                }
+                continue;
+            }
+
+            if event.event.is_close() {
+                if let Some(summary) = self.summary.take() {
+                    let summary = TraceEvent {
+                        level: event.level + 1,
+                        location: event.location,
+                        context: None,
+                        input: event.input.clone(),
+                        event: TraceEventType::Summary(summary),
+                    };
+
+                    #[cfg(feature = "trace-print")]
+                    self.push(summary, silent);
+                    #[cfg(not(feature = "trace-print"))]
+                    self.push(summary);
+                }
+            }
+
+            #[cfg(feature = "trace-print")]
+            self.push(event, silent);
+            #[cfg(not(feature = "trace-print"))]
+            self.push(event);
+        }
+    }
+
+    /// Sets which events the trace keeps, dropping the ones it doesn't keep anymore.
+    pub fn set_retention(&mut self, retention: TraceRetention) {
+        self.retention = retention;
+        self.unresolved = self.events.len();
+        self.invocations.clear();
+        if let TraceRetention::LastInvocations(_) = retention {
+            let removed = self.removed;
+            let previous = std::iter::once(None).chain(self.events.iter().map(Some));
+            self.invocations.extend(
+                self.events
+                    .iter()
+                    .zip(previous)
+                    .enumerate()
+                    .filter(|(_, (event, previous))| starts_invocation(event, *previous))
+                    .map(|(i, _)| removed + i),
+            );
+        }
+        let from = self.retained_from();
+        self.drop_events(from);
+    }
+
+    /// Takes the recorded events out of the trace.
+    pub(crate) fn take_events(&mut self) -> VecDeque<TraceEvent> {
+        self.removed += self.events.len();
+        self.invocations.clear();
+        std::mem::take(&mut self.events)
+    }
+
+    /// Adds an event to the trace, prints it if needed, and drops the events the retention
+    /// doesn't keep anymore.
+    fn push(&mut self, event: TraceEvent, #[cfg(feature = "trace-print")] silent: bool) {
+        let failures_only = self.retention == TraceRetention::FailuresOnly;
+
+        // With the failures-only retention, events are printed once their invocation failed.
+        #[cfg(feature = "trace-print")]
+        if self.print && !silent && !failures_only {
+            self.sink.write(&event.to_string());
+        }
+
+        // Invocations can only be dropped when a new one starts.
+        let check = match self.retention {
+            TraceRetention::All | TraceRetention::FailuresOnly => false,
+            TraceRetention::LastEvents(_) => true,
+            TraceRetention::LastInvocations(_) => starts_invocation(&event, self.events.back()),
+        };
+        if check && matches!(self.retention, TraceRetention::LastInvocations(_)) {
+            self.invocations.push_back(self.removed + self.events.len());
+        }
+        let failed = if failures_only && event.level == 0 {
+            invocation_failed(&event.event)
+        } else {
+            None
+        };
+
+        self.events.push_back(event);
+        if check {
+            let from = self.retained_from();
+            self.drop_events(from);
+        }
+
+        match failed {
+            Some(true) => {
+                #[cfg(feature = "trace-print")]
+                if self.print && !silent {
+                    for event in self.events.range(self.unresolved..) {
+                        self.sink.write(&event.to_string());
+                    }
+                }
+                self.unresolved = self.events.len();
+            }
+            Some(false) => {
+                self.dropped += self.events.len() - self.unresolved;
+                self.events.truncate(self.unresolved);
+            }
+            None => {}
+        }
+    }
+
+    /// Returns the index of the first event the retention keeps, forgetting the invocations
+    /// it doesn't keep anymore.
+    fn retained_from(&mut self) -> usize {
+        match self.retention {
+            TraceRetention::All | TraceRetention::FailuresOnly => 0,
+            TraceRetention::LastEvents(count) => self.events.len().saturating_sub(count),
+            TraceRetention::LastInvocations(count) => {
+                while self.invocations.len() > count {
+                    self.invocations.pop_front();
+                }
+                match self.invocations.front() {
+                    Some(start) if self.invocations.len() == count => start - self.removed,
+                    None if count == 0 => self.events.len(),
+                    _ => 0,
+                }
+            }
+        }
+    }
+
+    /// Drops the first events, and counts them as dropped.
+    fn drop_events(&mut self, count: usize) {
+        for _ in 0..count {
+            self.events.pop_front();
+        }
+        self.removed += count;
+        self.dropped += count;
+    }
+
     /// Sets the current nesting level of the trace.
     ///
     /// # Arguments
@@ -161,11 +558,54 @@ impl Trace {
     }
 }
 
+/// Returns `true` if the event starts a top-level invocation, given the event before it.
+///
+/// A memo miss starts the invocation of the parser it is followed by, so the open event of that
+/// parser doesn't start another one.
+fn starts_invocation(event: &TraceEvent, previous: Option<&TraceEvent>) -> bool {
+    event.level == 0
+        && !event.event.is_close()
+        && !previous.is_some_and(|previous| {
+            previous.level == 0 && previous.event == TraceEventType::MemoMiss
+        })
+}
+
+/// Returns whether a top-level event ends a failed invocation, or `None` if it doesn't end an
+/// invocation.
+fn invocation_failed(event: &TraceEventType) -> Option<bool> {
+    match event {
+        TraceEventType::Open | TraceEventType::MemoMiss | TraceEventType::Summary(_) => None,
+        TraceEventType::CloseOk(_) => Some(false),
+        TraceEventType::MemoHit(result) | TraceEventType::Silenced { result, .. } => {
+            Some(!matches!(**result, TraceEventType::CloseOk(_)))
+        }
+        _ => Some(true),
+    }
+}
+
+/// Converts the result of a parser into the matching close event type.
+pub(crate) fn close_event_type<I, O: Debug, E: Debug>(result: &IResult<I, O, E>) -> TraceEventType {
+    match result {
+        Ok((_, o)) => TraceEventType::CloseOk(format!("{:?}", o)),
+        Err(nom::Err::Error(e)) => TraceEventType::CloseError(format!("{:?}", e)),
+        Err(nom::Err::Failure(e)) => TraceEventType::CloseFailure(format!("{:?}", e)),
+        Err(nom::Err::Incomplete(i)) => TraceEventType::CloseIncomplete(*i),
+    }
+}
+
 impl Display for Trace {
     /// Formats the entire trace for display.
     ///
-    /// This will format and display all events in the trace sequentially.
+    /// This will format and display all events in the trace sequentially,
+    /// or the statistics table if the trace is in statistics-only mode.
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(stats) = &self.stats {
+            return Display::fmt(stats, f);
+        }
+
+        if self.dropped > 0 {
+            writeln!(f, "({} events dropped)", self.dropped)?;
+        }
         for event in self.events.iter() {
             event.fmt(f)?;
         }
@@ -194,7 +634,7 @@ mod tests {
     #[test]
     fn test_trace_clear() {
         let mut trace = Trace::default();
-        trace.events.push(TraceEvent {
+        trace.events.push_back(TraceEvent {
             level: 0,
             location: "test",
             context: None,
@@ -232,6 +672,174 @@ mod tests {
         assert!(matches!(trace.events[1].event, TraceEventType::CloseOk(_)));
     }
 
+    #[test]
+    fn test_trace_retain_last_events() {
+        let mut trace = Trace::default();
+        trace.set_retention(TraceRetention::LastEvents(3));
+        for _ in 0..3 {
+            trace.open(None, "input", "location", false);
+        }
+        assert_eq!(trace.dropped, 0);
+
+        trace.open(None, "input", "last", false);
+        assert_eq!(trace.events.len(), 3);
+        assert_eq!(trace.dropped, 1);
+        assert_eq!(trace.events[2].location, "last");
+        assert!(trace.to_string().starts_with("(1 events dropped)\n"));
+
+        trace.set_retention(TraceRetention::LastEvents(1));
+        assert_eq!(trace.events.len(), 1);
+        assert_eq!(trace.dropped, 3);
+
+        trace.clear();
+        assert_eq!(trace.dropped, 0);
+    }
+
+    #[test]
+    fn test_trace_retain_last_invocations() {
+        let mut trace = Trace::default();
+        trace.set_retention(TraceRetention::LastInvocations(2));
+
+        for input in ["a", "b", "c"] {
+            trace.open(None, input, "outer", false);
+            trace.open(None, input, "inner", false);
+            trace.close::<_, _, nom::error::VerboseError<&str>>(
+                None,
+                input,
+                "inner",
+                &Ok(("", input)),
+                false,
+            );
+            trace.close::<_, _, nom::error::VerboseError<&str>>(
+                None,
+                input,
+                "outer",
+                &Ok(("", input)),
+                false,
+            );
+        }
+
+        assert_eq!(trace.events.len(), 8);
+        assert_eq!(trace.dropped, 4);
+        assert_eq!(trace.events[0].input, "b");
+        assert_eq!(trace.events[4].input, "c");
+
+        trace.set_retention(TraceRetention::LastInvocations(1));
+        assert_eq!(trace.events.len(), 4);
+        assert_eq!(trace.events[0].input, "c");
+
+        trace.open(None, "d", "outer", false);
+        assert_eq!(trace.events.len(), 1);
+        assert_eq!(trace.dropped, 12);
+    }
+
+    #[test]
+    fn test_trace_retain_failures_only() {
+        type Error<'a> = nom::error::VerboseError<&'a str>;
+
+        let mut trace = Trace::default();
+        trace.set_retention(TraceRetention::FailuresOnly);
+
+        for input in ["ok", "error", "ok"] {
+            trace.open(None, input, "outer", false);
+            trace.open(None, input, "inner", false);
+            trace.close::<_, _, Error>(None, input, "inner", &Ok(("", input)), false);
+            let result: IResult<_, &str, Error> = match input {
+                "ok" => Ok(("", input)),
+                _ => Err(nom::Err::Error(Error { errors: vec![] })),
+            };
+            trace.close(None, input, "outer", &result, false);
+        }
+
+        assert_eq!(trace.events.len(), 4);
+        assert!(trace.events.iter().all(|event| event.input == "error"));
+        assert_eq!(trace.dropped, 8);
+    }
+
+    #[test]
+    fn test_trace_record_depth() {
+        type Error<'a> = nom::error::VerboseError<&'a str>;
+
+        let mut trace = Trace {
+            record_depth: Some(1),
+            ..Default::default()
+        };
+
+        trace.open(None, "input", "outer", false);
+        trace.open(None, "input", "middle", false);
+        for (location, result) in [
+            ("first", Ok(("", "input"))),
+            ("second", Err(nom::Err::Error(Error { errors: vec![] }))),
+        ] {
+            trace.open(None, "input", location, false);
+            trace.open(None, "input", "leaf", false);
+            trace.close::<_, _, Error>(None, "input", "leaf", &Ok(("", "input")), false);
+            trace.close(None, "input", location, &result, false);
+        }
+        trace.close::<_, _, Error>(None, "", "middle", &Ok(("", "input")), false);
+        trace.close::<_, _, Error>(None, "", "outer", &Ok(("", "input")), false);
+
+        assert_eq!(trace.level, 0);
+        assert_eq!(trace.events.len(), 5);
+        assert_eq!(
+            trace.events[2].event,
+            TraceEventType::Summary(TraceSummary {
+                invocations: 4,
+                max_level: 3,
+                ok: 3,
+                error: 1,
+                ..TraceSummary::default()
+            })
+        );
+        assert_eq!(trace.events[2].level, 2);
+        assert_eq!(trace.events[3].location, "middle");
+    }
+
+    #[cfg(feature = "trace-silencing")]
+    #[test]
+    fn test_trace_expand_silenced() {
+        let event = |level, location, event| TraceEvent {
+            level,
+            location,
+            context: None,
+            input: "input".to_string(),
+            event,
+        };
+        let error = || TraceEventType::CloseError("error".to_string());
+        let silenced = [
+            event(5, "silenced", TraceEventType::Open),
+            event(6, "nested", TraceEventType::Open),
+            event(7, "leaf", TraceEventType::Open),
+            event(7, "leaf", error()),
+            event(6, "nested", error()),
+            event(5, "silenced", error()),
+        ];
+
+        let mut trace = Trace {
+            silence: TraceSilence::ExpandOnFailure,
+            record_depth: Some(1),
+            ..Default::default()
+        };
+        trace.open(None, "input", "outer", false);
+        #[cfg(feature = "trace-print")]
+        trace.silenced(&silenced, false);
+        #[cfg(not(feature = "trace-print"))]
+        trace.silenced(&silenced);
+
+        let levels: Vec<_> = trace.events.iter().map(|event| event.level).collect();
+        assert_eq!(levels, [0, 1, 2, 1]);
+        assert_eq!(
+            trace.events[2].event,
+            TraceEventType::Summary(TraceSummary {
+                invocations: 2,
+                max_level: 3,
+                error: 2,
+                ..TraceSummary::default()
+            })
+        );
+        assert_eq!(trace.events[3].location, "silenced");
+    }
+
     #[test]
     fn test_trace_set_level() {
         let mut trace = Trace::default();
@@ -239,6 +847,93 @@ mod tests {
         assert_eq!(trace.level, 5);
     }
 
+    #[test]
+    fn test_trace_stats_mode() {
+        let mut trace = Trace {
+            stats: Some(TraceStats::default()),
+            ..Default::default()
+        };
+        trace.open(None, "input", "outer", false);
+        trace.open(None, "input", "inner", false);
+        trace.close::<_, _, nom::error::VerboseError<&str>>(
+            None,
+            "input",
+            "inner",
+            &Ok(("ut", "inp")),
+            false,
+        );
+        trace.close::<_, _, nom::error::VerboseError<&str>>(
+            None,
+            "input",
+            "outer",
+            &Ok(("ut", "inp")),
+            false,
+        );
+
+        assert!(trace.events.is_empty());
+        assert_eq!(trace.level, 0);
+
+        let stats = trace.stats.as_ref().unwrap();
+        assert_eq!(stats.locations["inner"].max_depth, 1);
+        assert_eq!(stats.locations["inner"].consumed, 3);
+        assert!(trace.to_string().starts_with("location"));
+
+        trace.clear();
+        assert!(trace.stats.as_ref().unwrap().locations.is_empty());
+    }
+
+    #[cfg(feature = "trace-memo")]
+    mod memo_tests {
+        use super::*;
+
+        #[test]
+        fn test_trace_memo() {
+            let mut trace = Trace::default();
+            trace.open(None, "input", "outer", false);
+            trace.memo::<_, &str, nom::error::VerboseError<&str>>(
+                None, "input", "location", None, false,
+            );
+            let level = trace.memo::<_, _, nom::error::VerboseError<&str>>(
+                None,
+                "input",
+                "location",
+                Some(&Ok(("", "result"))),
+                false,
+            );
+
+            assert_eq!(level, 1);
+            assert_eq!(trace.events.len(), 3);
+            assert_eq!(trace.events[1].level, 1);
+            assert!(matches!(trace.events[1].event, TraceEventType::MemoMiss));
+            assert!(matches!(
+                &trace.events[2].event,
+                TraceEventType::MemoHit(result) if matches!(**result, TraceEventType::CloseOk(_))
+            ));
+        }
+
+        #[test]
+        fn test_trace_memo_last_invocations() {
+            type Error<'a> = nom::error::VerboseError<&'a str>;
+
+            let mut trace = Trace::default();
+            trace.set_retention(TraceRetention::LastInvocations(1));
+
+            for input in ["a", "b"] {
+                trace.memo::<_, &str, Error>(None, input, "memo", None, false);
+                trace.open(None, input, "parser", false);
+                trace.close::<_, _, Error>(None, input, "parser", &Ok(("", input)), false);
+            }
+
+            assert_eq!(trace.events.len(), 3);
+            assert!(matches!(trace.events[0].event, TraceEventType::MemoMiss));
+            assert_eq!(trace.events[0].input, "b");
+            assert_eq!(trace.dropped, 3);
+
+            trace.set_retention(TraceRetention::LastInvocations(1));
+            assert_eq!(trace.events.len(), 3);
+        }
+    }
+
     #[cfg(feature = "trace-max-level")]
     mod max_level_tests {
         use super::*;
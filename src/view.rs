@@ -0,0 +1,273 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+//! Alternative renderings of a trace.
+//!
+//! On large inputs, a full trace is thousands of lines long. A [TraceView] renders only the
+//! part of the trace needed to answer a question, like why a parse failed. Views are selected
+//! with [get_trace!](crate::get_trace) and [print_trace!](crate::print_trace):
+//!
+//! ```
+//! use nom_tracer::{get_trace, trace, view::TraceView};
+//! use nom::{branch::alt, bytes::complete::tag, IResult};
+//!
+//! fn keyword(input: &str) -> IResult<&str, &str> {
+//!     trace!(alt((tag("true"), tag("false"))))(input)
+//! }
+//!
+//! if keyword("maybe").is_err() {
+//!     if let Some(trace) = get_trace!(view = TraceView::FailurePath) {
+//!         println!("{}", trace);
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "trace")]
+use {
+    crate::{events::TraceEventType, frames::Frame, traces::Trace},
+    std::fmt::Write,
+};
+
+/// A rendering of a trace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceView {
+    /// Every event, as rendered by the `Display` implementation of the trace.
+    #[default]
+    Full,
+    /// The chain of parsers from the root down to the deepest failing leaf parser.
+    ///
+    /// At each level, the alternatives tried alongside the parser on the chain are shown with
+    /// their result, and the parsers nested in them are collapsed. Only the top-level parse
+    /// containing the failure is rendered. If no leaf parser failed, nothing is rendered.
+    FailurePath,
+    /// The successful derivation, which reads like a parse tree.
+    ///
+    /// The parsers that failed inside a successful parser, like the alternatives of an `alt`
+    /// that didn't match, were backtracked over: they are dropped with the parsers nested in
+    /// them. Memoization misses are dropped too. Inside a failed parser, the failures are kept.
+    Derivation,
+}
+
+/// Renders a trace with the given view.
+///
+/// Statistics-only traces are always rendered as their statistics table.
+#[cfg(feature = "trace")]
+pub fn render(trace: &Trace, view: TraceView) -> String {
+    if trace.stats.is_some() {
+        return trace.to_string();
+    }
+
+    match view {
+        TraceView::Full => trace.to_string(),
+        TraceView::FailurePath => render_frames(trace, |output, frames| {
+            if let Some(path) = failure_path(frames) {
+                render_path(output, &path);
+            }
+        }),
+        TraceView::Derivation => render_frames(trace, |output, frames| {
+            for frame in frames {
+                render_derivation(output, frame);
+            }
+        }),
+    }
+}
+
+/// Renders the frames of a trace with the given function, after the number of dropped events.
+#[cfg(feature = "trace")]
+fn render_frames(trace: &Trace, f: impl FnOnce(&mut String, &[Frame])) -> String {
+    let mut output = String::new();
+    if trace.dropped > 0 {
+        writeln!(output, "({} events dropped)", trace.dropped).unwrap();
+    }
+
+    f(&mut output, &Frame::from_events(&trace.events));
+    output
+}
+
+/// Returns the chain of frames from a root down to the deepest failing leaf, if any.
+///
+/// Among the leaves at the same depth, the last one wins.
+#[cfg(feature = "trace")]
+fn failure_path<'f, 'a>(frames: &'f [Frame<'a>]) -> Option<Vec<&'f Frame<'a>>> {
+    fn visit<'f, 'a>(
+        frame: &'f Frame<'a>,
+        path: &mut Vec<&'f Frame<'a>>,
+        best: &mut Option<Vec<&'f Frame<'a>>>,
+    ) {
+        path.push(frame);
+        if frame.is_leaf()
+            && frame.is_failure()
+            && best.as_ref().is_none_or(|best| path.len() >= best.len())
+        {
+            *best = Some(path.clone());
+        }
+        for child in &frame.children {
+            visit(child, path, best);
+        }
+        path.pop();
+    }
+
+    let mut best = None;
+    for root in frames {
+        visit(root, &mut Vec::new(), &mut best);
+    }
+    best
+}
+
+/// Renders the frames of a chain, with the siblings of each frame collapsed.
+#[cfg(feature = "trace")]
+fn render_path(output: &mut String, path: &[&Frame]) {
+    let Some((frame, rest)) = path.split_first() else {
+        return;
+    };
+
+    write!(output, "{}", frame.open).unwrap();
+    for child in &frame.children {
+        match rest.first() {
+            Some(next) if std::ptr::eq(child, *next) => render_path(output, rest),
+            _ => render_collapsed(output, child),
+        }
+    }
+    if let Some(close) = frame.close {
+        write!(output, "{}", close).unwrap();
+    }
+}
+
+/// Renders a frame with the frames nested in it collapsed into a single line.
+#[cfg(feature = "trace")]
+fn render_collapsed(output: &mut String, frame: &Frame) {
+    write!(output, "{}", frame.open).unwrap();
+
+    let nested = frame.iter().count() - 1;
+    if nested > 0 {
+        writeln!(
+            output,
+            "{}… {} nested invocations collapsed",
+            "| ".repeat(frame.open.level + 1),
+            nested
+        )
+        .unwrap();
+    }
+
+    if let Some(close) = frame.close {
+        write!(output, "{}", close).unwrap();
+    }
+}
+
+/// Renders a frame without the parsers that were backtracked over.
+#[cfg(feature = "trace")]
+fn render_derivation(output: &mut String, frame: &Frame) {
+    if matches!(frame.open.event, TraceEventType::MemoMiss) {
+        return;
+    }
+
+    write!(output, "{}", frame.open).unwrap();
+    let succeeded = matches!(frame.result(), Some(TraceEventType::CloseOk(_)));
+    for child in &frame.children {
+        if !(succeeded && child.is_failure()) {
+            render_derivation(output, child);
+        }
+    }
+    if let Some(close) = frame.close {
+        write!(output, "{}", close).unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use {
+        super::*,
+        crate::{get_trace, reset_trace, trace, DEFAULT_TAG, TRACE_TAGS},
+        nom::{
+            branch::alt,
+            bytes::complete::tag,
+            sequence::{pair, preceded},
+            IResult,
+        },
+    };
+
+    fn key(input: &str) -> IResult<&str, &str> {
+        trace!(alt((|i| trace!(tag("a"))(i), tag("b"))))(input)
+    }
+
+    fn value(input: &str) -> IResult<&str, &str> {
+        trace!(alt((
+            |i| trace!(tag("true"))(i),
+            |i| trace!(preceded(tag("\""), tag("\"")))(i)
+        )))(input)
+    }
+
+    fn entry(input: &str) -> IResult<&str, (&str, &str)> {
+        trace!(pair(key, |i| trace!(preceded(tag("="), value))(i)))(input)
+    }
+
+    #[test]
+    fn test_failure_path() {
+        reset_trace!();
+        assert!(entry("a=x").is_err());
+
+        let rendered = TRACE_TAGS.with(|tags| {
+            tags.borrow()
+                .get_trace_view(DEFAULT_TAG, TraceView::FailurePath)
+                .unwrap()
+        });
+        let lines: Vec<_> = rendered.lines().collect();
+
+        // The key parser succeeded: its nested parsers are collapsed.
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("1 nested invocations collapsed")));
+        // The deepest failure is in the value alternatives, both of which are shown.
+        assert_eq!(
+            lines.iter().filter(|line| line.contains("value")).count(),
+            3
+        );
+        assert_eq!(lines.first().map(|line| line.contains("entry")), Some(true));
+    }
+
+    #[test]
+    fn test_failure_path_without_failure() {
+        reset_trace!();
+        assert!(entry("a=true").is_ok());
+
+        let rendered = TRACE_TAGS.with(|tags| {
+            tags.borrow()
+                .get_trace_view(DEFAULT_TAG, TraceView::FailurePath)
+                .unwrap()
+        });
+        assert!(rendered.is_empty());
+    }
+
+    #[test]
+    fn test_derivation() {
+        reset_trace!();
+        assert!(entry("b=\"\"").is_ok());
+
+        let rendered = TRACE_TAGS.with(|tags| {
+            tags.borrow()
+                .get_trace_view(DEFAULT_TAG, TraceView::Derivation)
+                .unwrap()
+        });
+
+        // The failed alternatives of `key` and `value` were backtracked over.
+        assert!(!rendered.contains("Error"));
+        assert_eq!(rendered.matches("-> Ok").count(), 5);
+        assert_eq!(
+            rendered
+                .lines()
+                .filter(|line| line.contains("value"))
+                .count(),
+            2
+        );
+
+        // Inside a failed parser, the failures are kept.
+        reset_trace!();
+        assert!(entry("a=x").is_err());
+        let derivation = TRACE_TAGS.with(|tags| {
+            tags.borrow()
+                .get_trace_view(DEFAULT_TAG, TraceView::Derivation)
+                .unwrap()
+        });
+        assert_eq!(derivation, get_trace!().unwrap());
+    }
+}
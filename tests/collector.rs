@@ -0,0 +1,129 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+// A collector is installed for all the threads of the process, so these tests live in their
+// own test binary, where they can't capture the traces of unrelated tests.
+
+#[cfg(feature = "trace")]
+mod collector_tests {
+    use {
+        nom::{bytes::complete::tag, IResult},
+        nom_tracer::{
+            collector::{self, TraceCollector},
+            context::TraceContext,
+            get_trace,
+            reset_trace,
+            session::TraceSession,
+            trace,
+            tracer::Tracer,
+            DEFAULT_TAG,
+        },
+        std::sync::Mutex,
+    };
+
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn hello(input: &str) -> IResult<&str, &str> {
+        trace!(tag("hello"))(input)
+    }
+
+    #[test]
+    fn test_collect_from_threads() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+
+        let collector = TraceCollector::new();
+        collector.install();
+
+        std::thread::scope(|scope| {
+            for document in ["a.txt", "b.txt", "c.txt"] {
+                scope.spawn(move || {
+                    collector::with_document(document, || {
+                        let _ = hello("hello");
+                        let _ = hello("world");
+                    })
+                });
+            }
+        });
+
+        TraceCollector::uninstall();
+
+        let traces = collector.take();
+        assert!(collector.is_empty());
+        assert_eq!(traces.len(), 6);
+
+        let a: Vec<_> = traces
+            .iter()
+            .filter(|trace| trace.document.as_deref() == Some("a.txt"))
+            .collect();
+        assert_eq!(a.len(), 2);
+        assert_eq!(a[0].thread, a[1].thread);
+        assert_eq!(a[0].tag, DEFAULT_TAG);
+        assert_eq!(a[0].trace.events.len(), 2);
+    }
+
+    #[test]
+    fn test_flush() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+
+        reset_trace!();
+        let _ = hello("hello");
+
+        let collector = TraceCollector::new();
+        collector.install();
+        collector::set_document(Some("flushed"));
+        collector::flush();
+        collector::set_document(None::<String>);
+        TraceCollector::uninstall();
+
+        let traces = collector.take();
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].document.as_deref(), Some("flushed"));
+        assert_eq!(get_trace!().unwrap(), "");
+    }
+
+    #[test]
+    fn test_isolated_traces_are_not_collected() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+
+        let collector = TraceCollector::new();
+        collector.install();
+
+        let context = TraceContext::new();
+        let _ = context.enter(|| hello("hello"));
+
+        let session = TraceSession::start();
+        let _ = hello("hello");
+        collector::flush();
+        let tags = session.finish();
+
+        TraceCollector::uninstall();
+
+        assert!(collector.is_empty());
+        assert_eq!(
+            context.with(|tags| tags.traces[DEFAULT_TAG].events.len()),
+            2
+        );
+        assert_eq!(tags.traces[DEFAULT_TAG].events.len(), 2);
+    }
+
+    #[test]
+    fn test_tracer_is_not_collected() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+
+        let collector = TraceCollector::new();
+        collector.install();
+
+        let tracer = Tracer::new();
+        let _ = tracer.wrap("hello", hello)("hello");
+
+        TraceCollector::uninstall();
+
+        assert!(collector.is_empty());
+        assert_eq!(
+            tracer
+                .context()
+                .with(|tags| tags.traces[DEFAULT_TAG].events.len()),
+            4
+        );
+    }
+}
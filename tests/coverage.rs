@@ -0,0 +1,36 @@
+// Copyright (c) Hexbee
+// SPDX-License-Identifier: Apache-2.0
+
+// Coverage is collected for all the threads of the process, so this test lives in its own test
+// binary, where unrelated tests can't start or stop the collection while it runs.
+
+#[cfg(feature = "trace-coverage")]
+mod coverage_tests {
+    use {
+        nom::{bytes::complete::tag, error::VerboseError},
+        nom_tracer::{coverage, tr, DEFAULT_TAG},
+    };
+
+    #[test]
+    fn test_global_coverage() {
+        coverage::reset();
+        coverage::start();
+        let _ = tr(
+            DEFAULT_TAG,
+            None,
+            "test_global_coverage",
+            tag::<_, _, VerboseError<_>>("hello"),
+        )("hello world");
+        coverage::stop();
+        let _ = tr(
+            DEFAULT_TAG,
+            None,
+            "test_global_coverage",
+            tag::<_, _, VerboseError<_>>("hello"),
+        )("world");
+
+        let entry = coverage::global().entries[&("test_global_coverage", None)];
+        assert_eq!(entry.invocations, 1);
+        assert_eq!(entry.ok, 1);
+    }
+}
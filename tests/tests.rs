@@ -52,6 +52,145 @@ mod trace_tests {
         assert!(trace.contains("-> Ok"));
     }
 
+    #[test]
+    fn test_runtime_tags() {
+        for file in ["a.txt", "b.txt"] {
+            let tag = format!("runtime:{}", file);
+            reset_trace!(tag = tag);
+            let result = trace!(tag = tag.clone(), "file", parse_ab)("ab");
+            assert!(result.is_ok());
+
+            let trace = get_trace!(tag = tag).unwrap();
+            assert!(trace.contains("test_runtime_tags"));
+            assert!(trace.contains("file"));
+        }
+
+        let tags = list_tags();
+        assert!(tags.contains(&"runtime:a.txt".to_string()));
+        assert!(remove_tag("runtime:a.txt").is_some());
+        assert!(get_trace!(tag = "runtime:a.txt").is_none());
+
+        let drained = drain_tags();
+        assert!(drained.iter().any(|(tag, _)| tag == "runtime:b.txt"));
+        assert!(list_tags().is_empty());
+    }
+
+    #[test]
+    fn test_hierarchical_tags() {
+        deactivate_trace!(tag = "json.*");
+
+        let _ = trace!(tag = "json.string", parse_ab)("ab");
+        let _ = trace!(tag = "http.header", parse_ab)("ab");
+        assert!(get_trace!(tag = "json.string").unwrap().is_empty());
+        assert!(!get_trace!(tag = "http.header").unwrap().is_empty());
+
+        activate_trace!(tag = "json.*");
+        let _ = trace!(tag = "json.string", parse_ab)("ab");
+        assert!(!get_trace!(tag = "json.string").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_trace_config() {
+        set_trace_config!(
+            tag = "config.*",
+            nom_tracer::config::TraceConfig::new().active(false)
+        );
+        let _ = trace!(tag = "config.parser", parse_ab)("ab");
+        assert!(get_trace!(tag = "config.parser").unwrap().is_empty());
+
+        set_trace_config!(tag = "config.*", Default::default());
+        let _ = trace!(tag = "config.parser", parse_ab)("ab");
+        assert!(!get_trace!(tag = "config.parser").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trace_filters() {
+        use nom_tracer::filters::{TraceFilter, TraceFilters};
+
+        fn ws(input: &str) -> IResult<&str, &str> {
+            trace!(tag = "filtered", nom::character::complete::multispace0)(input)
+        }
+
+        fn a_then_b(input: &str) -> IResult<&str, (&str, &str, &str)> {
+            trace!(tag = "filtered", tuple((tag("a"), ws, tag("b"))))(input)
+        }
+
+        set_trace_filters!(
+            tag = "filtered",
+            TraceFilters::new().exclude(TraceFilter::name("ws"))
+        );
+        assert!(a_then_b("a b").is_ok());
+
+        let trace = get_trace!(tag = "filtered").unwrap();
+        assert!(trace.contains("a_then_b"));
+        assert!(!trace.contains("ws"));
+    }
+
+    #[test]
+    fn test_record_depth() {
+        fn nested(input: &str) -> IResult<&str, (&str, &str)> {
+            trace!(
+                tag = "shallow",
+                tuple((
+                    |i| trace!(tag = "shallow", tag("a"))(i),
+                    |i| trace!(tag = "shallow", tag("b"))(i)
+                ))
+            )(input)
+        }
+
+        set_record_depth!(tag = "shallow", Some(0));
+        assert!(nested("ab").is_ok());
+
+        let trace = get_trace!(tag = "shallow").unwrap();
+        assert_eq!(trace.lines().count(), 3);
+        assert!(trace.contains("… 2 nested invocations, max level 1: 2 ok"));
+    }
+
+    #[test]
+    fn test_failure_path_view() {
+        reset_trace!(views);
+        let _ = trace!(views, parse_ab)("ax");
+
+        let full = get_trace!(views).unwrap();
+        let failure_path = get_trace!(views, view = view::TraceView::FailurePath).unwrap();
+        assert_eq!(failure_path, full);
+        assert!(get_trace!(views, view = view::TraceView::Full).is_some());
+        assert!(get_trace!(nonexistent, view = view::TraceView::FailurePath).is_none());
+    }
+
+    #[test]
+    fn test_assert_trace() {
+        fn parse_traced_ab(input: &str) -> IResult<&str, (&str, &str)> {
+            trace!(tuple((
+                |i| trace!("a", tag("a"))(i),
+                |i| trace!("b", tag("b"))(i)
+            )))(input)
+        }
+
+        reset_trace!();
+        reset_trace!(assertions_tag);
+        let result = trace!(assertions_tag, parse_traced_ab)("ab");
+        assert!(result.is_ok());
+        let result = parse_traced_ab("aa");
+        assert!(result.is_err());
+
+        assert_trace!(assertions_tag, |trace| {
+            trace
+                .parser("test_assert_trace")
+                .invoked_times(1)
+                .never_failed();
+        });
+        assert_trace!(|trace| {
+            trace.max_invocations(2);
+            trace.parser("a").invoked_with("aa").never_failed();
+            trace
+                .parser("b")
+                .invoked_with("b")
+                .returned_ok(1)
+                .returned_error(1);
+        });
+    }
+
     #[test]
     fn test_activate_deactivate_reset() {
         activate_trace!();
@@ -130,6 +269,25 @@ mod trace_silencing_tests {
 
         let trace = get_trace!().unwrap();
         assert!(trace.contains("outer"));
+        assert_eq!(trace.matches("[inner]").count(), 1);
+        assert!(trace.contains("silenced, 0 nested invocations hidden"));
+    }
+
+    #[test]
+    fn test_silence_tree_hidden() {
+        fn outer_parser(input: &str) -> IResult<&str, (&str, &str)> {
+            trace!(
+                quiet,
+                "outer",
+                tuple((tag("a"), silence_tree!(quiet, "inner", tag("b"))))
+            )(input)
+        }
+
+        set_trace_silence!(quiet, config::TraceSilence::Hide);
+        assert!(outer_parser("ab").is_ok());
+
+        let trace = get_trace!(quiet).unwrap();
+        assert!(trace.contains("outer"));
         assert!(!trace.contains("inner"));
     }
 }